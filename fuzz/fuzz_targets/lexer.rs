@@ -0,0 +1,16 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use whenever_parser::lexer::{self, Cursor, Lexer, TokenVariant};
+
+fuzz_target!(|src: &str| {
+    let mut cursor = Cursor::new(src);
+    while let Ok((token, next)) = lexer::eat(cursor)
+    {
+        if token.variant == TokenVariant::EOI
+        {
+            break;
+        }
+        cursor = next;
+    }
+    for _ in Lexer::with_trivia(src) {}
+});
@@ -0,0 +1,15 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use whenever_parser::format::{self, Style};
+
+fuzz_target!(|src: &str| {
+    if let Ok(line) = whenever_parser::parse_line(src)
+    {
+        // Whatever parses can be formatted and parsed again
+        let formatted = format::format_line(&line, &Style::default());
+        if let Err(error) = whenever_parser::parse_line(&formatted)
+        {
+            panic!("{} in {:?}, formatted from {:?}", error, formatted, src);
+        }
+    }
+});
@@ -1,10 +1,11 @@
 // Grammar:
 // The grammar has been refined so that alternations and sequences don't mix,
-// allowing to express all alternations through traits and all sequences
+// allowing to express all alternations through enums and all sequences
 // through structs.
 // Lowercase means nonterminal, uppercase means terminal (tokens).
 //
 // Root:
+// program := line*
 // line := NUMBER statement SEMICOLON
 //
 // number := absolutenumber | unopnum | binopnum | parensnum | stringtonum
@@ -16,12 +17,18 @@
 // binopnum := number binmathop number
 // parensnum := LPAREN number RPAREN
 //
+// Binary operators are left associative, MATHOP binding tighter than PLUS and
+// MINUS. Unary operators bind tighter than any binary operator.
+//
 // boolean := unopbool | binopbool | binopnumbool | parensbool | numtobool
 // unopbool := UNBOOLOP boolean
 // binopbool := boolean BINBOOLOP boolean
 // binopnumbool := number BINNUMBOOLOP number
 // parensbool := LPAREN boolean RPAREN
 //
+// UNBOOLOP binds tighter than BINNUMBOOLOP, which binds tighter than `&&`,
+// which binds tighter than `||`. Binary operators are left associative.
+//
 // n := N LPAREN number RPAREN
 // read := READ LPAREN RPAREN
 //
@@ -38,9 +45,12 @@
 // print := PRINT LPAREN string RPAREN
 //
 // string := STRING | u | concat | numtostring
-// concat := string PLUS string
+// concat := string PLUS string // left associative
 // u := U LPAREN absnum RPAREN
 //
+// A number on the right of a concat is read up to the next PLUS, which is
+// another concatenation.
+//
 // These conversion are not straightforward and will require their own nodes in
 // the AST:
 // numtobool := number
@@ -48,12 +58,19 @@
 // numtostring := number
 // stringtonum := string
 
-use std::ptr;
+pub mod owned;
+
+use std::mem;
+
+use crate::num::Int;
+use crate::span::Span;
 
 // Traits defining relationships between nodes
 pub trait Graph<'a> {
     /// Returns the range of characters spanned by the node.
     fn get_str(&self) -> &'a str;
+    /// Returns the bytes of the input spanned by the node.
+    fn span(&self) -> Span;
     /// Returns a text representation of the node.
     fn get_label(&self) -> String;
     /// Generates arrows for each children and returns the concatenation of the
@@ -61,91 +78,291 @@ pub trait Graph<'a> {
     ///
     /// TODO: Find a non-intrusive fashion to do the same thing.
     fn to_dot_recurse(&self) -> String;
+    /// Returns the children of the node, in order.
+    ///
+    /// Nodes without children are asked for `to_dot_recurse` instead.
+    fn children(&self) -> Vec<&dyn Graph<'a>> { Vec::new() }
 
     /// Returns a unique identifier for this node.
     fn get_id(&self) -> String;
     /// Returns a representation of the subtree of `self` in Graphiz' dot
     /// format.
+    ///
+    /// The subtree is walked with an explicit stack, as chains of operators
+    /// are nested as deep as they are long.
     fn to_dot(&self) -> String
     {
-        let id = self.get_id();
-        let mut dot =
-            format!("  \"{}\" [label={}];\n", id, self.get_label());
-        dot += self.to_dot_recurse().as_str();
-        return dot;
+        let mut dot = String::new();
+        let mut stack = Vec::new();
+        push_dot(&mut dot, &mut stack, self);
+        while let Some((parent, node)) = stack.pop()
+        {
+            dot += format!("  \"{}\" -> \"{}\";\n",
+                           parent, node.get_id()).as_str();
+            push_dot(&mut dot, &mut stack, node);
+        }
+        dot
     }
 }
 
-/// Returns the output of `to_dot()` with identifiers normalized for
-/// consistency.
-///
-/// Replaces all occurrences of pointers with their offset from
-/// `root.get_str().as_ptr()`.
-pub fn to_dot_normalized<'a>(root: &dyn Graph<'a>) -> String
+// Writes the declaration of `node` to `dot`, and pushes its children onto
+// `stack` along with its identifier, so that the first one is popped first
+fn push_dot<'a, 'n, G>(dot: &mut String,
+                       stack: &mut Vec<(String, &'n dyn Graph<'a>)>,
+                       node: &'n G)
+    where G: Graph<'a> + ?Sized
 {
-    let dot = root.to_dot();
-    let base = root.get_str().as_ptr() as usize;
-
-    let hexsize = format!("{:x}", base).len(); // TODO: atoi maybe ?
-    let mut split = dot.split("0x");
-
-    let mut normalized = String::from(split.next().unwrap());
-
-    for abnormal in split
+    let id = node.get_id();
+    *dot += format!("  \"{}\" [label={}];\n", id, node.get_label()).as_str();
+    let children = node.children();
+    if children.is_empty()
     {
-        let (addr, remainder) = abnormal.split_at(hexsize);
-        let offset = usize::from_str_radix(addr, 16).unwrap() - base;
-        normalized += format!("{:#x}", offset).as_str();
-        normalized += remainder;
+        *dot += node.to_dot_recurse().as_str();
     }
+    stack.extend(children.into_iter().rev().map(|child| (id.clone(), child)));
+}
+
+// Nodes as they appear in the graph, where alternations are the node they hold
+trait AsGraph<'a>
+{
+    fn as_graph(&self) -> &dyn Graph<'a>;
+}
+impl<'a, G: Graph<'a>> AsGraph<'a> for G
+{
+    fn as_graph(&self) -> &dyn Graph<'a> { self }
+}
 
-    normalized
+/// Returns the identifier of the node spanning `span`.
+///
+/// Identifiers are made of the offset and length of the span, so that they
+/// only depend on the input. Nonterminals spanning the same bytes as one of
+/// their children add their name to tell them apart.
+fn span_id(span: Span) -> String
+{
+    format!("{:#x}_{}", span.start, span.len())
+}
+
+/// Returns the output of `to_dot()`.
+///
+/// Identifiers used to be addresses in memory, which this normalized into
+/// offsets. They are now offsets to begin with.
+#[deprecated(note = "use `Graph::to_dot` instead")]
+pub fn to_dot_normalized<'a>(root: &dyn Graph<'a>) -> String
+{
+    root.to_dot()
 }
 
-// Traits for all alternations, and struct wrappers
+// Enums for all alternations, and struct wrappers
 //
-// Alternation structs are not part of the graph, they are just here to wrap
-// around a Box<> and provide a consistent interface.
+// Each alternation is an enum with a variant per possible node, so that nodes
+// can be told apart by pattern matching. Alternation structs are not part of
+// the graph, they are just here to wrap around the enum and provide a
+// consistent interface.
 trait Alternation<'a>
 {
     fn get_str(&self) -> &'a str;
+    fn span(&self) -> Span;
     fn get_id(&self) -> String;
     fn to_dot(&self) -> String;
 }
 macro_rules! define_alternation
 {
-    ($name: ident, $altname: ident) =>
+    ($name: ident, $kind: ident, $($variant: ident, $type: ident),*) =>
+    {
+        define_alternation!(@node $name, $kind, $($variant, $type),*);
+        impl<'a> $name<'a>
+        {
+            /// Returns the alternative, consuming the node.
+            pub fn into_alt(self) -> $kind<'a> { self.alt }
+        }
+    };
+    // Chains of `$chain` nodes nest as deep as they are long, through their
+    // `$field`s, so dropping a long one recursively would overflow the stack.
+    // Links are detached from their parent instead, leaving a placeholder
+    // behind, and dropped one at a time.
+    ($name: ident, $kind: ident, $($variant: ident, $type: ident),*;
+     $chain: ident, $link: ident, ($($field: ident),*), $placeholder: ident) =>
+    {
+        define_alternation!(@node $name, $kind, $($variant, $type),*);
+        impl<'a> $name<'a>
+        {
+            /// Returns the alternative, consuming the node.
+            pub fn into_alt(mut self) -> $kind<'a>
+            {
+                mem::replace(&mut self.alt, $placeholder())
+            }
+
+            // Pushes the link held by the node, if any, onto `links`
+            fn detach(&mut self, links: &mut Vec<Box<$link<'a>>>)
+            {
+                if matches!(self.alt, $kind::$chain(_))
+                {
+                    if let $kind::$chain(link) =
+                        mem::replace(&mut self.alt, $placeholder())
+                    {
+                        links.push(link);
+                    }
+                }
+            }
+        }
+        impl Drop for $name<'_>
+        {
+            fn drop(&mut self)
+            {
+                let mut links = Vec::new();
+                if let $kind::$chain(link) = &mut self.alt
+                {
+                    $(link.$field.detach(&mut links);)*
+                }
+                while let Some(mut link) = links.pop()
+                {
+                    $(link.$field.detach(&mut links);)*
+                }
+            }
+        }
+    };
+    (@node $name: ident, $kind: ident, $($variant: ident, $type: ident),*) =>
     {
-        pub trait $altname<'a>: Graph<'a> {}
+        pub enum $kind<'a>
+        {
+            $($variant(Box<$type<'a>>),)*
+        }
         pub struct $name<'a>
         {
-            pub alt: Box<dyn $altname<'a> + 'a>
+            pub alt: $kind<'a>
+        }
+        impl<'a> Graph<'a> for $kind<'a>
+        {
+            fn get_str(&self) -> &'a str
+            {
+                match self { $($kind::$variant(node) => node.get_str(),)* }
+            }
+            fn span(&self) -> Span
+            {
+                match self { $($kind::$variant(node) => node.span(),)* }
+            }
+            fn get_id(&self) -> String
+            {
+                match self { $($kind::$variant(node) => node.get_id(),)* }
+            }
+            fn get_label(&self) -> String
+            {
+                match self { $($kind::$variant(node) => node.get_label(),)* }
+            }
+            fn to_dot_recurse(&self) -> String
+            {
+                match self
+                {
+                    $($kind::$variant(node) => node.to_dot_recurse(),)*
+                }
+            }
+            fn children(&self) -> Vec<&dyn Graph<'a>>
+            {
+                match self { $($kind::$variant(node) => node.children(),)* }
+            }
+        }
+        impl<'a> AsGraph<'a> for $name<'a>
+        {
+            fn as_graph(&self) -> &dyn Graph<'a> { &self.alt }
         }
         impl<'a> Alternation<'a> for $name<'a>
         {
             fn get_str(&self) -> &'a str { self.alt.get_str() }
+            fn span(&self) -> Span { self.alt.span() }
             fn get_id(&self) -> String { self.alt.get_id() }
             fn to_dot(&self) -> String { self.alt.to_dot() }
         }
+        $(
+            impl<'a> From<$type<'a>> for $name<'a>
+            {
+                fn from(node: $type<'a>) -> $name<'a>
+                {
+                    $name { alt: $kind::$variant(Box::new(node)) }
+                }
+            }
+        )*
     }
 }
-macro_rules! implement_alternations
+
+define_alternation!(Number, NumberKind, Absolute, AbsoluteNumber,
+                                        UnOp, UnOpNumber,
+                                        BinOp, BinOpNumber,
+                                        Parens, ParensNumber,
+                                        StringToNum, StringToNum;
+                    BinOp, BinOpNumber, (num1, num2), number_placeholder);
+define_alternation!(AbsNumber, AbsNumberKind, Number, NumberToken,
+                                              N, N,
+                                              Read, Read);
+define_alternation!(UnMathOp, UnMathOpKind, Plus, PlusToken,
+                                            Minus, MinusToken);
+define_alternation!(BinMathOp, BinMathOpKind, Plus, PlusToken,
+                                              Minus, MinusToken,
+                                              MathOp, MathOpToken);
+define_alternation!(Boolean, BooleanKind, UnOp, UnOpBoolean,
+                                          BinOp, BinOpBoolean,
+                                          BinOpNum, BinOpNumBoolean,
+                                          Parens, ParensBoolean,
+                                          NumToBool, NumToBool;
+                    BinOp, BinOpBoolean, (boolean1, boolean2),
+                    boolean_placeholder);
+define_alternation!(Statement, StatementKind, LineOperations, LineOperations,
+                                              Again, Again,
+                                              Defer, Defer,
+                                              Forget, Forget,
+                                              Print, Print);
+define_alternation!(LineOps, LineOpsKind, LineOp, LineOp,
+                                          List, LineOpList;
+                    List, LineOpList, (list), lineops_placeholder);
+define_alternation!(SingleLineOp, SingleLineOpKind, NumToLineOp, NumToLineOp,
+                                                    Count, CountLineOp);
+// Fixing collision with String is too much work
+define_alternation!(String_, StringKind, String, StringToken,
+                                         U, U,
+                                         Concat, Concat,
+                                         NumToString, NumToString;
+                    Concat, Concat, (str1, str2), string_placeholder);
+
+// Placeholders left behind when the alternative of a chain is moved out, which
+// are dropped right after
+
+fn number_placeholder<'a>() -> NumberKind<'a>
 {
-    ($name: ident, $($alt: ident),*) =>
-    {
-        $(impl<'a> $alt<'a> for $name<'a> {})*
-    }
+    let num = NumberToken {
+        tok: "",
+        span: Span::new(0, 0),
+        val: Int::default()
+    };
+    NumberKind::Absolute(Box::new(AbsoluteNumber {
+        src: "",
+        span: num.span,
+        num: AbsNumber::from(num)
+    }))
+}
+
+fn boolean_placeholder<'a>() -> BooleanKind<'a>
+{
+    let num = Number { alt: number_placeholder() };
+    BooleanKind::NumToBool(Box::new(NumToBool { num }))
 }
 
-define_alternation!(Number, NumberAlt);
-define_alternation!(AbsNumber, AbsNumAlt);
-define_alternation!(UnMathOp, UnMathOpAlt);
-define_alternation!(BinMathOp, BinMathOpAlt);
-define_alternation!(Boolean, BoolAlt);
-define_alternation!(Statement, StatementAlt);
-define_alternation!(LineOps, LineOpsAlt);
-define_alternation!(SingleLineOp, SingleLineOpAlt);
-define_alternation!(String_, StringAlt); // Fixing collision is too much work
+fn lineops_placeholder<'a>() -> LineOpsKind<'a>
+{
+    let num = Number { alt: number_placeholder() };
+    LineOpsKind::LineOp(Box::new(LineOp {
+        src: "",
+        span: Span::new(0, 0),
+        slo: SingleLineOp::from(NumToLineOp { num })
+    }))
+}
+
+fn string_placeholder<'a>() -> StringKind<'a>
+{
+    StringKind::String(Box::new(StringToken {
+        tok: "",
+        span: Span::new(0, 0),
+        val: String::new()
+    }))
+}
 
 // Structs defining terminals
 macro_rules! define_terminal
@@ -154,14 +371,16 @@ macro_rules! define_terminal
     {
         pub struct $name<'a>
         {
-            pub tok: &'a str
+            pub tok: &'a str,
+            pub span: Span
         }
         impl<'a> Graph<'a> for $name<'a>
         {
             fn get_str(&self) -> &'a str { self.tok }
+            fn span(&self) -> Span { self.span }
             fn get_id(&self) -> String
             {
-                format!("{:p}_{}", self.tok.as_ptr(), self.tok.len())
+                span_id(self.span)
             }
             fn get_label(&self) -> String { format!("\"{}\"", self.tok) }
             fn to_dot_recurse(&self) -> String { String::new() }
@@ -178,17 +397,9 @@ define_terminal!(ReadToken);
 define_terminal!(UToken);
 
 define_terminal!(PlusToken);
-implement_alternations!(PlusToken, UnMathOpAlt, BinMathOpAlt);
 define_terminal!(MinusToken);
-implement_alternations!(MinusToken, UnMathOpAlt, BinMathOpAlt);
 
-define_terminal!(StringToken);
-implement_alternations!(StringToken, StringAlt);
 define_terminal!(UnBoolOpToken);
-define_terminal!(BinBoolOpToken);
-define_terminal!(BinNumBoolOpToken);
-define_terminal!(MathOpToken);
-implement_alternations!(MathOpToken, BinMathOpAlt);
 
 define_terminal!(CommaToken);
 define_terminal!(LeftParensToken);
@@ -196,17 +407,76 @@ define_terminal!(RightParensToken);
 define_terminal!(SemicolonToken);
 define_terminal!(SharpToken);
 
+/// A comparison between numbers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CmpOp
+{
+    Lt, // <
+    Le, // <=
+    Gt, // >
+    Ge, // >=
+    Eq, // ==
+    Ne // !=
+}
+
+/// A binary boolean operator.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BoolOp
+{
+    And, // &&
+    Or // ||
+}
+
+/// A multiplicative operator.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MulOp
+{
+    Mul, // *
+    Div // /
+}
+
+// Operator terminals also carry the operator they stand for
+macro_rules! define_op_terminal
+{
+    ($name: ident, $op: ident) =>
+    {
+        pub struct $name<'a>
+        {
+            pub tok: &'a str,
+            pub span: Span,
+            pub val: $op
+        }
+        impl<'a> Graph<'a> for $name<'a>
+        {
+            fn get_str(&self) -> &'a str { self.tok }
+            fn span(&self) -> Span { self.span }
+            fn get_id(&self) -> String
+            {
+                span_id(self.span)
+            }
+            fn get_label(&self) -> String { format!("\"{}\"", self.tok) }
+            fn to_dot_recurse(&self) -> String { String::new() }
+        }
+    };
+}
+
+define_op_terminal!(BinBoolOpToken, BoolOp);
+define_op_terminal!(BinNumBoolOpToken, CmpOp);
+define_op_terminal!(MathOpToken, MulOp);
+
 pub struct NumberToken<'a>
 {
     pub tok: &'a str,
-    pub val: usize
+    pub span: Span,
+    pub val: Int
 }
 impl<'a> Graph<'a> for NumberToken<'a>
 {
     fn get_str(&self) -> &'a str { self.tok }
+    fn span(&self) -> Span { self.span }
     fn get_id(&self) -> String
     {
-        format!("{:p}_{}", self.tok.as_ptr(), self.tok.len())
+        span_id(self.span)
     }
     fn get_label(&self) -> String
     {
@@ -214,64 +484,62 @@ impl<'a> Graph<'a> for NumberToken<'a>
     }
     fn to_dot_recurse(&self) -> String { String::new() }
 }
-implement_alternations!(NumberToken, AbsNumAlt);
+
+pub struct StringToken<'a>
+{
+    pub tok: &'a str,
+    pub span: Span,
+    /// The string, without quotes and with escape sequences decoded.
+    pub val: String
+}
+impl<'a> Graph<'a> for StringToken<'a>
+{
+    fn get_str(&self) -> &'a str { self.tok }
+    fn span(&self) -> Span { self.span }
+    fn get_id(&self) -> String
+    {
+        span_id(self.span)
+    }
+    fn get_label(&self) -> String { format!("\"{}\"", self.tok) }
+    fn to_dot_recurse(&self) -> String { String::new() }
+}
 
 // Structs defining nonterminals
+//
+// A nonterminal spans from the start of its first child to the end of its
+// last one.
 macro_rules! define_nonterminal
 {
     ($name: ident, $($field: ident, $fieldtype: ident),*) =>
     {
         pub struct $name<'a>
         {
-            pub range: &'a str,
+            /// The whole input the node was parsed from.
+            pub src: &'a str,
+            pub span: Span,
             $(pub $field: $fieldtype<'a>,)*
         }
         impl<'a> $name<'a>
         {
-            pub fn new($($field: $fieldtype<'a>,)*) -> $name<'a>
+            pub fn new(src: &'a str, $($field: $fieldtype<'a>,)*) -> $name<'a>
             {
-                let mut minptr : *const u8 = ptr::null();
-                let mut minpos = std::usize::MAX;
-                let mut maxpos = 0usize;
-                $(
-                    let s = $field.get_str();
-                    let ptr = s.as_ptr();
-                    let pos = ptr as usize;
-
-                    if pos < minpos
-                    {
-                        minptr = ptr;
-                        minpos = pos;
-                    }
-                    let endpos = pos + s.len();
-                    if maxpos < endpos
-                    {
-                        maxpos = endpos;
-                    }
-                 )*
-                let len = maxpos - minpos;
-                if minptr.is_null() || len < 1
-                {
-                    panic!("Invalid range: {:p} len: {}", minptr, len);
-                }
-                let range : &'a str = unsafe {
-                    let slice = std::slice::from_raw_parts(minptr,
-                                                           len as usize);
-                    std::str::from_utf8(slice).unwrap()
-                };
+                let spans = [$($field.span(),)*];
+                let start = spans.iter().map(|span| span.start).min();
+                let end = spans.iter().map(|span| span.end).max();
                 $name {
-                    range,
+                    src,
+                    span: Span::new(start.unwrap(), end.unwrap()),
                     $($field,)*
                 }
             }
         }
         impl<'a> Graph<'a> for $name<'a>
         {
-            fn get_str(&self) -> &'a str { self.range }
+            fn get_str(&self) -> &'a str { &self.src[self.span.range()] }
+            fn span(&self) -> Span { self.span }
             fn get_id(&self) -> String
             {
-                format!(concat!("{:p}_{}_", stringify!($name)),
-                        self.range.as_ptr(), self.range.len())
+                span_id(self.span) + concat!("_", stringify!($name))
             }
             fn get_label(&self) -> String
             {
@@ -288,6 +556,10 @@ macro_rules! define_nonterminal
                 )*
                 res
             }
+            fn children(&self) -> Vec<&dyn Graph<'a>>
+            {
+                vec![$(self.$field.as_graph(),)*]
+            }
         }
     }
 }
@@ -295,86 +567,66 @@ define_nonterminal!(Line, num, NumberToken,
                           stmt, Statement,
                           semi, SemicolonToken);
 define_nonterminal!(AbsoluteNumber, num, AbsNumber);
-implement_alternations!(AbsoluteNumber, NumberAlt);
 define_nonterminal!(UnOpNumber, op, UnMathOp,
                                 num, Number);
-implement_alternations!(UnOpNumber, NumberAlt);
 define_nonterminal!(BinOpNumber, num1, Number,
                                  op, BinMathOp,
                                  num2, Number);
-implement_alternations!(BinOpNumber, NumberAlt);
 define_nonterminal!(ParensNumber, lparen, LeftParensToken,
                                   num, Number,
                                   rparen, RightParensToken);
-implement_alternations!(ParensNumber, NumberAlt);
 define_nonterminal!(UnOpBoolean, op, UnBoolOpToken,
                                  boolean, Boolean);
-implement_alternations!(UnOpBoolean, BoolAlt);
 define_nonterminal!(BinOpBoolean, boolean1, Boolean,
                                   op, BinBoolOpToken,
                                   boolean2, Boolean);
-implement_alternations!(BinOpBoolean, BoolAlt);
 define_nonterminal!(BinOpNumBoolean, num1, Number,
                                      op, BinNumBoolOpToken,
                                      num2, Number);
-implement_alternations!(BinOpNumBoolean, BoolAlt);
 define_nonterminal!(ParensBoolean, lparen, LeftParensToken,
                                    boolean, Boolean,
                                    rparen, RightParensToken);
-implement_alternations!(ParensBoolean, BoolAlt);
 define_nonterminal!(N, keyword, NToken,
                        lparen, LeftParensToken,
                        num, Number,
                        rparen, RightParensToken);
-implement_alternations!(N, AbsNumAlt);
 define_nonterminal!(Read, keyword, ReadToken,
                           lparen, LeftParensToken,
                           rparen, RightParensToken);
-implement_alternations!(Read, AbsNumAlt);
 define_nonterminal!(LineOperations, lineops, LineOps);
-implement_alternations!(LineOperations, StatementAlt);
 define_nonterminal!(LineOp, slo, SingleLineOp);
-implement_alternations!(LineOp, LineOpsAlt);
 define_nonterminal!(CountLineOp, line, Number,
                                  sharp, SharpToken,
                                  count, Number);
-implement_alternations!(CountLineOp, SingleLineOpAlt);
 define_nonterminal!(LineOpList, lineop, LineOp,
                                 comma, CommaToken,
                                 list, LineOps);
-implement_alternations!(LineOpList, LineOpsAlt);
 define_nonterminal!(Again, keyword, AgainToken,
                            lparen, LeftParensToken,
                            boolean, Boolean,
                            rparen, RightParensToken,
                            statement, Statement);
-implement_alternations!(Again, StatementAlt);
 define_nonterminal!(Defer, keyword, DeferToken,
                            lparen, LeftParensToken,
                            boolean, Boolean,
                            rparen, RightParensToken,
                            statement, Statement);
-implement_alternations!(Defer, StatementAlt);
 define_nonterminal!(Forget, keyword, ForgetToken,
                             lparen, LeftParensToken,
                             boolean, Boolean,
                             rparen, RightParensToken,
                             statement, Statement);
-implement_alternations!(Forget, StatementAlt);
 define_nonterminal!(Print, keyword, PrintToken,
                            lparen, LeftParensToken,
                            string, String_,
                            rparen, RightParensToken);
-implement_alternations!(Print, StatementAlt);
 define_nonterminal!(Concat, str1, String_,
                             op, PlusToken,
                             str2, String_);
-implement_alternations!(Concat, StringAlt);
 define_nonterminal!(U, keyword, UToken,
                        lparen, LeftParensToken,
                        num, AbsNumber,
                        rparen, RightParensToken);
-implement_alternations!(U, StringAlt);
 
 // Nonterminals for conversions
 macro_rules! define_conversion
@@ -388,6 +640,7 @@ macro_rules! define_conversion
         impl<'a> Graph<'a> for $name<'a>
         {
             fn get_str(&self) -> &'a str { self.$varname.get_str() }
+            fn span(&self) -> Span { self.$varname.span() }
             fn get_id(&self) -> String
             {
                 self.$varname.get_id() + concat!('_', stringify!($name))
@@ -403,15 +656,16 @@ macro_rules! define_conversion
                         self.$varname.get_id())
                     + self.$varname.to_dot().as_str()
             }
+            fn children(&self) -> Vec<&dyn Graph<'a>>
+            {
+                vec![self.$varname.as_graph()]
+            }
         }
     }
 }
 define_conversion!(NumToBool, Number, Boolean, num);
-implement_alternations!(NumToBool, BoolAlt);
 define_conversion!(NumToLineOp, Number, SingleLineOp, num);
-implement_alternations!(NumToLineOp, SingleLineOpAlt);
 define_conversion!(NumToString, Number, String, num);
-implement_alternations!(NumToString, StringAlt);
 pub struct StringToNum<'a>
 {
     pub string: String_<'a>
@@ -419,6 +673,7 @@ pub struct StringToNum<'a>
 impl<'a> Graph<'a> for StringToNum<'a>
 {
     fn get_str(&self) -> &'a str { self.string.get_str() }
+    fn span(&self) -> Span { self.string.span() }
     fn get_id(&self) -> String { self.string.get_id() + "_StringToNum" }
     fn get_label(&self) -> String { String::from("<<I>String ⮕ Number</I>>") }
     fn to_dot_recurse(&self) -> String
@@ -426,5 +681,149 @@ impl<'a> Graph<'a> for StringToNum<'a>
         format!("  \"{}\" -> \"{}\";\n", self.get_id(), self.string.get_id())
             + self.string.to_dot().as_str()
     }
+    fn children(&self) -> Vec<&dyn Graph<'a>>
+    {
+        vec![self.string.as_graph()]
+    }
+}
+
+// Root of a whole program
+//
+// A program holds a variable number of lines, which `define_nonterminal!` does
+// not handle. It spans the rest of the input, including leading and trailing
+// whitespace. Comments are not part of the grammar, they are kept aside and
+// do not appear in the graph.
+pub struct Program<'a>
+{
+    /// The whole input the program was parsed from.
+    pub src: &'a str,
+    pub span: Span,
+    pub lines: Vec<ProgramLine<'a>>,
+    /// Every comment of the program, in order.
+    pub comments: Vec<Comment<'a>>
+}
+impl<'a> Graph<'a> for Program<'a>
+{
+    fn get_str(&self) -> &'a str { &self.src[self.span.range()] }
+    fn span(&self) -> Span { self.span }
+    fn get_id(&self) -> String
+    {
+        span_id(self.span) + "_Program"
+    }
+    fn get_label(&self) -> String { String::from("<<I>Program</I>>") }
+    fn to_dot_recurse(&self) -> String
+    {
+        let mut res = String::new();
+        let id = self.get_id();
+        for line in &self.lines
+        {
+            res += format!("  \"{}\" -> \"{}\";\n",
+                           id, line.get_id()).as_str();
+            res += line.to_dot().as_str();
+        }
+        res
+    }
+    fn children(&self) -> Vec<&dyn Graph<'a>>
+    {
+        self.lines.iter().map(|line| line as &dyn Graph<'a>).collect()
+    }
+}
+
+/// A line of a program, or what is left of it when it could not be parsed.
+pub enum ProgramLine<'a>
+{
+    Line(Line<'a>),
+    Error(ErrorLine<'a>)
+}
+impl<'a> ProgramLine<'a>
+{
+    /// Returns the line, if it could be parsed.
+    pub fn as_line(&self) -> Option<&Line<'a>>
+    {
+        match self
+        {
+            ProgramLine::Line(line) => Some(line),
+            ProgramLine::Error(_) => None
+        }
+    }
+}
+impl<'a> Graph<'a> for ProgramLine<'a>
+{
+    fn get_str(&self) -> &'a str
+    {
+        match self
+        {
+            ProgramLine::Line(line) => line.get_str(),
+            ProgramLine::Error(error) => error.get_str()
+        }
+    }
+    fn span(&self) -> Span
+    {
+        match self
+        {
+            ProgramLine::Line(line) => line.span(),
+            ProgramLine::Error(error) => error.span()
+        }
+    }
+    fn get_id(&self) -> String
+    {
+        match self
+        {
+            ProgramLine::Line(line) => line.get_id(),
+            ProgramLine::Error(error) => error.get_id()
+        }
+    }
+    fn get_label(&self) -> String
+    {
+        match self
+        {
+            ProgramLine::Line(line) => line.get_label(),
+            ProgramLine::Error(error) => error.get_label()
+        }
+    }
+    fn to_dot_recurse(&self) -> String
+    {
+        match self
+        {
+            ProgramLine::Line(line) => line.to_dot_recurse(),
+            ProgramLine::Error(error) => error.to_dot_recurse()
+        }
+    }
+    fn children(&self) -> Vec<&dyn Graph<'a>>
+    {
+        match self
+        {
+            ProgramLine::Line(line) => line.children(),
+            ProgramLine::Error(error) => error.children()
+        }
+    }
+}
+
+/// The text of a line that could not be parsed, up to where parsing resumed.
+pub struct ErrorLine<'a>
+{
+    /// The whole input the line was parsed from.
+    pub src: &'a str,
+    pub span: Span
+}
+impl<'a> Graph<'a> for ErrorLine<'a>
+{
+    fn get_str(&self) -> &'a str { &self.src[self.span.range()] }
+    fn span(&self) -> Span { self.span }
+    fn get_id(&self) -> String
+    {
+        span_id(self.span) + "_ErrorLine"
+    }
+    fn get_label(&self) -> String { String::from("<<I>ErrorLine</I>>") }
+    fn to_dot_recurse(&self) -> String { String::new() }
+}
+
+/// A `//` or `/* */` comment, delimiters included.
+pub struct Comment<'a>
+{
+    pub tok: &'a str,
+    pub span: Span,
+    /// Index in `Program::lines` of the first line starting after the comment,
+    /// if any.
+    pub line: Option<usize>
 }
-implement_alternations!(StringToNum, NumberAlt);
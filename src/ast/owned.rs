@@ -0,0 +1,449 @@
+// Owned AST
+//
+// The same nodes as the parent module, without the lifetime of the input:
+// text is replaced by its span, and values are owned. Owned nodes can outlive
+// the input, be sent to other threads, and be built from scratch.
+//
+// `IntoOwned` converts the borrowed AST into its owned counterpart.
+
+use crate::ast::{self, BoolOp, CmpOp, Graph, MulOp};
+use crate::num::Int;
+use crate::span::Span;
+
+/// Conversion of a borrowed node into an owned one.
+pub trait IntoOwned
+{
+    type Owned;
+
+    /// Returns the owned counterpart of the node.
+    fn into_owned(self) -> Self::Owned;
+}
+
+macro_rules! define_alternation
+{
+    ($name: ident, $kind: ident, $($variant: ident, $type: ident),*) =>
+    {
+        #[derive(Clone, Debug, PartialEq, Eq)]
+        pub enum $kind
+        {
+            $($variant(Box<$type>),)*
+        }
+        #[derive(Clone, Debug, PartialEq, Eq)]
+        pub struct $name
+        {
+            pub alt: $kind
+        }
+        impl IntoOwned for ast::$name<'_>
+        {
+            type Owned = $name;
+
+            fn into_owned(self) -> $name
+            {
+                let alt = match self.into_alt()
+                {
+                    $(
+                        ast::$kind::$variant(node) =>
+                            $kind::$variant(Box::new(node.into_owned())),
+                    )*
+                };
+                $name { alt }
+            }
+        }
+        $(
+            impl From<$type> for $name
+            {
+                fn from(node: $type) -> $name
+                {
+                    $name { alt: $kind::$variant(Box::new(node)) }
+                }
+            }
+        )*
+    }
+}
+
+define_alternation!(Number, NumberKind, Absolute, AbsoluteNumber,
+                                        UnOp, UnOpNumber,
+                                        BinOp, BinOpNumber,
+                                        Parens, ParensNumber,
+                                        StringToNum, StringToNum);
+define_alternation!(AbsNumber, AbsNumberKind, Number, NumberToken,
+                                              N, N,
+                                              Read, Read);
+define_alternation!(UnMathOp, UnMathOpKind, Plus, PlusToken,
+                                            Minus, MinusToken);
+define_alternation!(BinMathOp, BinMathOpKind, Plus, PlusToken,
+                                              Minus, MinusToken,
+                                              MathOp, MathOpToken);
+define_alternation!(Boolean, BooleanKind, UnOp, UnOpBoolean,
+                                          BinOp, BinOpBoolean,
+                                          BinOpNum, BinOpNumBoolean,
+                                          Parens, ParensBoolean,
+                                          NumToBool, NumToBool);
+define_alternation!(Statement, StatementKind, LineOperations, LineOperations,
+                                              Again, Again,
+                                              Defer, Defer,
+                                              Forget, Forget,
+                                              Print, Print);
+define_alternation!(LineOps, LineOpsKind, LineOp, LineOp,
+                                          List, LineOpList);
+define_alternation!(SingleLineOp, SingleLineOpKind, NumToLineOp, NumToLineOp,
+                                                    Count, CountLineOp);
+define_alternation!(String_, StringKind, String, StringToken,
+                                         U, U,
+                                         Concat, Concat,
+                                         NumToString, NumToString);
+
+// Terminals keep their span, and their value if they have one
+macro_rules! define_terminal
+{
+    ($name: ident) =>
+    {
+        #[derive(Clone, Debug, PartialEq, Eq)]
+        pub struct $name
+        {
+            pub span: Span
+        }
+        impl IntoOwned for ast::$name<'_>
+        {
+            type Owned = $name;
+
+            fn into_owned(self) -> $name
+            {
+                $name { span: self.span }
+            }
+        }
+    };
+    ($name: ident, $valtype: ty) =>
+    {
+        #[derive(Clone, Debug, PartialEq, Eq)]
+        pub struct $name
+        {
+            pub span: Span,
+            pub val: $valtype
+        }
+        impl IntoOwned for ast::$name<'_>
+        {
+            type Owned = $name;
+
+            fn into_owned(self) -> $name
+            {
+                $name { span: self.span, val: self.val }
+            }
+        }
+    };
+}
+
+define_terminal!(AgainToken);
+define_terminal!(DeferToken);
+define_terminal!(ForgetToken);
+define_terminal!(NToken);
+define_terminal!(PrintToken);
+define_terminal!(ReadToken);
+define_terminal!(UToken);
+
+define_terminal!(PlusToken);
+define_terminal!(MinusToken);
+
+define_terminal!(UnBoolOpToken);
+define_terminal!(BinBoolOpToken, BoolOp);
+define_terminal!(BinNumBoolOpToken, CmpOp);
+define_terminal!(MathOpToken, MulOp);
+
+define_terminal!(CommaToken);
+define_terminal!(LeftParensToken);
+define_terminal!(RightParensToken);
+define_terminal!(SemicolonToken);
+define_terminal!(SharpToken);
+
+define_terminal!(NumberToken, Int);
+define_terminal!(StringToken, String);
+
+macro_rules! define_nonterminal
+{
+    ($name: ident, $($field: ident, $fieldtype: ident),*) =>
+    {
+        #[derive(Clone, Debug, PartialEq, Eq)]
+        pub struct $name
+        {
+            pub span: Span,
+            $(pub $field: $fieldtype,)*
+        }
+        impl IntoOwned for ast::$name<'_>
+        {
+            type Owned = $name;
+
+            fn into_owned(self) -> $name
+            {
+                $name {
+                    span: self.span,
+                    $($field: self.$field.into_owned(),)*
+                }
+            }
+        }
+    }
+}
+define_nonterminal!(Line, num, NumberToken,
+                          stmt, Statement,
+                          semi, SemicolonToken);
+define_nonterminal!(AbsoluteNumber, num, AbsNumber);
+define_nonterminal!(UnOpNumber, op, UnMathOp,
+                                num, Number);
+define_nonterminal!(BinOpNumber, num1, Number,
+                                 op, BinMathOp,
+                                 num2, Number);
+define_nonterminal!(ParensNumber, lparen, LeftParensToken,
+                                  num, Number,
+                                  rparen, RightParensToken);
+define_nonterminal!(UnOpBoolean, op, UnBoolOpToken,
+                                 boolean, Boolean);
+define_nonterminal!(BinOpBoolean, boolean1, Boolean,
+                                  op, BinBoolOpToken,
+                                  boolean2, Boolean);
+define_nonterminal!(BinOpNumBoolean, num1, Number,
+                                     op, BinNumBoolOpToken,
+                                     num2, Number);
+define_nonterminal!(ParensBoolean, lparen, LeftParensToken,
+                                   boolean, Boolean,
+                                   rparen, RightParensToken);
+define_nonterminal!(N, keyword, NToken,
+                       lparen, LeftParensToken,
+                       num, Number,
+                       rparen, RightParensToken);
+define_nonterminal!(Read, keyword, ReadToken,
+                          lparen, LeftParensToken,
+                          rparen, RightParensToken);
+define_nonterminal!(LineOperations, lineops, LineOps);
+define_nonterminal!(LineOp, slo, SingleLineOp);
+define_nonterminal!(CountLineOp, line, Number,
+                                 sharp, SharpToken,
+                                 count, Number);
+define_nonterminal!(LineOpList, lineop, LineOp,
+                                comma, CommaToken,
+                                list, LineOps);
+define_nonterminal!(Again, keyword, AgainToken,
+                           lparen, LeftParensToken,
+                           boolean, Boolean,
+                           rparen, RightParensToken,
+                           statement, Statement);
+define_nonterminal!(Defer, keyword, DeferToken,
+                           lparen, LeftParensToken,
+                           boolean, Boolean,
+                           rparen, RightParensToken,
+                           statement, Statement);
+define_nonterminal!(Forget, keyword, ForgetToken,
+                            lparen, LeftParensToken,
+                            boolean, Boolean,
+                            rparen, RightParensToken,
+                            statement, Statement);
+define_nonterminal!(Print, keyword, PrintToken,
+                           lparen, LeftParensToken,
+                           string, String_,
+                           rparen, RightParensToken);
+define_nonterminal!(Concat, str1, String_,
+                            op, PlusToken,
+                            str2, String_);
+define_nonterminal!(U, keyword, UToken,
+                       lparen, LeftParensToken,
+                       num, AbsNumber,
+                       rparen, RightParensToken);
+
+macro_rules! define_conversion
+{
+    ($name: ident, $from: ident, $varname: ident) =>
+    {
+        #[derive(Clone, Debug, PartialEq, Eq)]
+        pub struct $name
+        {
+            pub $varname: $from
+        }
+        impl IntoOwned for ast::$name<'_>
+        {
+            type Owned = $name;
+
+            fn into_owned(self) -> $name
+            {
+                $name { $varname: self.$varname.into_owned() }
+            }
+        }
+    }
+}
+define_conversion!(NumToBool, Number, num);
+define_conversion!(NumToLineOp, Number, num);
+define_conversion!(NumToString, Number, num);
+define_conversion!(StringToNum, String_, string);
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Program
+{
+    pub span: Span,
+    pub lines: Vec<ProgramLine>,
+    pub comments: Vec<Comment>
+}
+impl IntoOwned for ast::Program<'_>
+{
+    type Owned = Program;
+
+    fn into_owned(self) -> Program
+    {
+        Program {
+            span: self.span,
+            lines: self.lines.into_iter().map(|line| {
+                line.into_owned()
+            }).collect(),
+            comments: self.comments.into_iter().map(|comment| {
+                comment.into_owned()
+            }).collect()
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ProgramLine
+{
+    Line(Line),
+    Error(ErrorLine)
+}
+impl ProgramLine
+{
+    /// Returns the line, if it could be parsed.
+    pub fn as_line(&self) -> Option<&Line>
+    {
+        match self
+        {
+            ProgramLine::Line(line) => Some(line),
+            ProgramLine::Error(_) => None
+        }
+    }
+}
+impl IntoOwned for ast::ProgramLine<'_>
+{
+    type Owned = ProgramLine;
+
+    fn into_owned(self) -> ProgramLine
+    {
+        match self
+        {
+            ast::ProgramLine::Line(line) =>
+                ProgramLine::Line(line.into_owned()),
+            ast::ProgramLine::Error(error) =>
+                ProgramLine::Error(error.into_owned())
+        }
+    }
+}
+
+/// The text of a line that could not be parsed, up to where parsing resumed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ErrorLine
+{
+    pub span: Span,
+    pub text: String
+}
+impl IntoOwned for ast::ErrorLine<'_>
+{
+    type Owned = ErrorLine;
+
+    fn into_owned(self) -> ErrorLine
+    {
+        ErrorLine { span: self.span, text: String::from(self.get_str()) }
+    }
+}
+
+/// A `//` or `/* */` comment, delimiters included.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Comment
+{
+    pub span: Span,
+    pub text: String,
+    /// Index in `Program::lines` of the first line starting after the comment,
+    /// if any.
+    pub line: Option<usize>
+}
+impl IntoOwned for ast::Comment<'_>
+{
+    type Owned = Comment;
+
+    fn into_owned(self) -> Comment
+    {
+        Comment { span: self.span, text: String::from(self.tok),
+                  line: self.line }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::thread;
+
+    use crate::lexer;
+    use crate::parser::{eat_line, eat_program_recovering};
+
+    #[test]
+    fn into_owned_check()
+    {
+        let program = {
+            let input = String::from("// a\n1 print(\"a\\n\" + N(0x2));\n2 ?;");
+            let (program, _) =
+                eat_program_recovering(lexer::Cursor::new(&input));
+            program.into_owned()
+        };
+
+        // The owned program outlives its input, and can change threads
+        let program = thread::spawn(move || program).join().unwrap();
+
+        assert_eq!(program.span, Span::new(0, 34));
+        assert_eq!(program.comments,
+                   vec![Comment { span: Span::new(0, 4),
+                                  text: String::from("// a"),
+                                  line: Some(0) }]);
+        assert_eq!(program.lines[1],
+                   ProgramLine::Error(ErrorLine { span: Span::new(30, 34),
+                                                  text: String::from("2 ?;")
+                   }));
+        let line = program.lines[0].as_line().unwrap();
+        assert_eq!(line.span, Span::new(5, 29));
+        assert_eq!(line.num, NumberToken { span: Span::new(5, 6),
+                                           val: Int::from(1) });
+        let print = match &line.stmt.alt
+        {
+            StatementKind::Print(print) => print,
+            _ => panic!("Not a print")
+        };
+        let concat = match &print.string.alt
+        {
+            StringKind::Concat(concat) => concat,
+            _ => panic!("Not a concatenation")
+        };
+        assert_eq!(concat.span, Span::new(13, 27));
+        assert_eq!(concat.str1.alt,
+                   StringKind::String(Box::new(StringToken {
+                       span: Span::new(13, 18),
+                       val: String::from("a\n")
+                   })));
+    }
+
+    #[test]
+    fn build_check()
+    {
+        // 1 3;
+        let num = NumberToken { span: Span::new(2, 3), val: Int::from(3) };
+        let slo = SingleLineOp::from(NumToLineOp {
+            num: Number::from(AbsoluteNumber {
+                span: num.span,
+                num: AbsNumber::from(num)
+            })
+        });
+        let lineops = LineOps::from(LineOp { span: Span::new(2, 3), slo });
+        let built = Line {
+            span: Span::new(0, 4),
+            num: NumberToken { span: Span::new(0, 1), val: Int::from(1) },
+            stmt: Statement::from(LineOperations { span: Span::new(2, 3),
+                                                   lineops }),
+            semi: SemicolonToken { span: Span::new(3, 4) }
+        };
+
+        let input = "1 3;";
+        let (line, _) = eat_line(lexer::Cursor::new(input)).unwrap();
+        assert_eq!(line.into_owned(), built);
+    }
+}
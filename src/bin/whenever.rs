@@ -0,0 +1,370 @@
+// Command-line interface to the parser and the interpreter
+
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, BufReader, IsTerminal, Read, Write};
+use std::process;
+use std::str::FromStr;
+
+use whenever_parser::ast::{self, Graph};
+use whenever_parser::diagnostic::{self, Diagnostic};
+use whenever_parser::format::{self, Style};
+use whenever_parser::interp::Interpreter;
+use whenever_parser::io::{Io, StdIo, Streams};
+use whenever_parser::{lexer, parser};
+
+fn usage() -> String
+{
+    format!("\
+Usage: whenever <command> [options] <file>
+
+Reads the program from the standard input when <file> is `-`.
+
+Commands:
+    check              Report every error found without running the program
+    dot                Print the AST in Graphviz' dot format
+    fmt                Print the program formatted canonically
+    run                Execute the program
+
+Options:
+    --max-depth <n>    Allow nodes nested up to <n> levels deep [default: {}]
+
+Options for fmt:
+    --width <n>        Wrap lines longer than <n> characters [default: 80]
+    --indent <n>       Indent wrapped lines by <n> spaces [default: 4]
+    --compact          Do not put spaces around binary operators
+    --sort             Sort lines by number
+    --check            Print nothing, but fail if the file is not formatted
+    --write            Format the file in place
+
+Options for run:
+    --seed <n>         Pick lines pseudo-randomly from <n>, not from the time
+    --input <file>     Read input from <file>, not from the standard input
+    --steps <n>        Stop with an error after executing <n> lines
+", lexer::DEFAULT_MAX_DEPTH)
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Command
+{
+    Check,
+    Dot,
+    Fmt,
+    Run
+}
+
+struct Options
+{
+    command: Command,
+    path: String,
+    max_depth: usize,
+    style: Style,
+    check: bool,
+    write: bool,
+    seed: Option<u64>,
+    input: Option<String>,
+    steps: Option<u64>
+}
+
+/// Returns the value following the option `name`.
+fn value<T: FromStr>(args: &mut dyn Iterator<Item = String>, name: &str)
+    -> Result<T, String>
+{
+    let value = args.next()
+        .ok_or_else(|| format!("missing value for `{}`", name))?;
+    value.parse()
+        .map_err(|_| format!("invalid value `{}` for `{}`", value, name))
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>)
+    -> Result<Options, String>
+{
+    let command = match args.next().as_deref()
+    {
+        Some("check") => Command::Check,
+        Some("dot") => Command::Dot,
+        Some("fmt") => Command::Fmt,
+        Some("run") => Command::Run,
+        Some(command) => return Err(format!("unknown command `{}`", command)),
+        None => return Err(String::from("missing command"))
+    };
+    let mut options = Options {
+        command,
+        path: String::new(),
+        max_depth: lexer::DEFAULT_MAX_DEPTH,
+        style: Style::default(),
+        check: false,
+        write: false,
+        seed: None,
+        input: None,
+        steps: None
+    };
+    let mut path = None;
+    while let Some(arg) = args.next()
+    {
+        let args = &mut args;
+        match (command, arg.as_str())
+        {
+            (_, "--max-depth") =>
+                options.max_depth = value(args, "--max-depth")?,
+            (Command::Fmt, "--width") =>
+                options.style.width = value(args, "--width")?,
+            (Command::Fmt, "--indent") =>
+                options.style.indent = value(args, "--indent")?,
+            (Command::Fmt, "--compact") =>
+                options.style.spaced_operators = false,
+            (Command::Fmt, "--sort") => options.style.sort_lines = true,
+            (Command::Fmt, "--check") => options.check = true,
+            (Command::Fmt, "--write") => options.write = true,
+            (Command::Run, "--seed") =>
+                options.seed = Some(value(args, "--seed")?),
+            (Command::Run, "--input") =>
+                options.input = Some(value(args, "--input")?),
+            (Command::Run, "--steps") =>
+                options.steps = Some(value(args, "--steps")?),
+            (_, option) if option.starts_with("--") =>
+                return Err(format!("unknown option `{}`", option)),
+            (_, _) if path.is_some() =>
+                return Err(format!("unexpected argument `{}`", arg)),
+            (_, _) => path = Some(arg)
+        }
+    }
+    if options.check && options.write
+    {
+        return Err(String::from("`--check` and `--write` cannot be used \
+                                 together"));
+    }
+    options.path = path.ok_or_else(|| String::from("missing file"))?;
+    Ok(options)
+}
+
+// A program and what is needed to report errors in it
+struct Source
+{
+    name: String,
+    text: String,
+    style: diagnostic::Style
+}
+
+impl Source
+{
+    fn read(path: &str) -> io::Result<Source>
+    {
+        let (name, text) = if path == "-"
+        {
+            let mut text = String::new();
+            io::stdin().read_to_string(&mut text)?;
+            (String::from("<stdin>"), text)
+        }
+        else
+        {
+            (String::from(path), fs::read_to_string(path)?)
+        };
+        let style = if io::stderr().is_terminal()
+            && env::var_os("NO_COLOR").is_none()
+        {
+            diagnostic::Style::Ansi
+        }
+        else
+        {
+            diagnostic::Style::Plain
+        };
+        Ok(Source { name, text, style })
+    }
+
+    fn report(&self, diagnostic: Diagnostic)
+    {
+        eprint!("{}", diagnostic.render(&self.text, Some(&self.name),
+                                        self.style));
+    }
+
+    fn cursor(&self, options: &Options) -> lexer::Cursor<'_>
+    {
+        lexer::Cursor::new(&self.text).with_max_depth(options.max_depth)
+    }
+
+    /// Returns the program, or reports why it could not be parsed.
+    fn parse(&self, options: &Options) -> Option<ast::Program<'_>>
+    {
+        match parser::eat_program(self.cursor(options))
+        {
+            Ok((program, _)) => Some(program),
+            Err(error) =>
+            {
+                self.report(Diagnostic::from(&error));
+                None
+            }
+        }
+    }
+}
+
+// Every command returns whether it succeeded
+
+fn check(source: &Source, options: &Options) -> bool
+{
+    let (program, errors) = parser::eat_program_recovering(
+        source.cursor(options));
+    for error in &errors
+    {
+        source.report(Diagnostic::from(error));
+    }
+    if !errors.is_empty()
+    {
+        return false;
+    }
+    // Lines must also have distinct numbers that fit in memory
+    match Interpreter::with_seed(&program, 0)
+    {
+        Ok(_) => true,
+        Err(error) =>
+        {
+            source.report(Diagnostic::from(&error));
+            false
+        }
+    }
+}
+
+fn dot(source: &Source, options: &Options) -> bool
+{
+    match source.parse(options)
+    {
+        Some(program) =>
+        {
+            print!("digraph {{\n{}}}\n", program.to_dot());
+            true
+        }
+        None => false
+    }
+}
+
+fn fmt(source: &Source, options: &Options) -> bool
+{
+    let program = match source.parse(options)
+    {
+        Some(program) => program,
+        None => return false
+    };
+    let formatted = format::format_program(&program, &options.style);
+    if options.check
+    {
+        if formatted != source.text
+        {
+            eprintln!("whenever: {} is not formatted", source.name);
+            return false;
+        }
+    }
+    else if options.write && options.path != "-"
+    {
+        if formatted != source.text
+        {
+            if let Err(error) = fs::write(&options.path, formatted)
+            {
+                eprintln!("whenever: cannot write {}: {}", source.name, error);
+                return false;
+            }
+        }
+    }
+    else
+    {
+        print!("{}", formatted);
+    }
+    true
+}
+
+fn run(source: &Source, options: &Options) -> bool
+{
+    let program = match source.parse(options)
+    {
+        Some(program) => program,
+        None => return false
+    };
+    let interpreter = match options.seed
+    {
+        Some(seed) => Interpreter::with_seed(&program, seed),
+        None => Interpreter::new(&program)
+    };
+    let mut interpreter = match interpreter
+    {
+        Ok(interpreter) => interpreter,
+        Err(error) =>
+        {
+            source.report(Diagnostic::from(&error));
+            return false;
+        }
+    };
+    let mut io: Box<dyn Io> = match &options.input
+    {
+        Some(path) => match File::open(path)
+        {
+            Ok(file) => Box::new(Streams::new(BufReader::new(file),
+                                              io::stdout())),
+            Err(error) =>
+            {
+                eprintln!("whenever: cannot read {}: {}", path, error);
+                return false;
+            }
+        },
+        None => Box::new(StdIo)
+    };
+
+    let mut steps = 0;
+    loop
+    {
+        if options.steps == Some(steps) && !interpreter.is_done()
+        {
+            eprintln!("whenever: stopped after {} steps, with lines left to \
+                       execute", steps);
+            return false;
+        }
+        match interpreter.step(io.as_mut())
+        {
+            Ok(Some(_)) => steps += 1,
+            Ok(None) => return true,
+            Err(error) =>
+            {
+                source.report(Diagnostic::from(&error));
+                return false;
+            }
+        }
+    }
+}
+
+fn main()
+{
+    let mut args = env::args().skip(1).peekable();
+    if let Some("-h") | Some("--help") | Some("help") =
+        args.peek().map(String::as_str)
+    {
+        print!("{}", usage());
+        return;
+    }
+    let options = match parse_args(args)
+    {
+        Ok(options) => options,
+        Err(message) =>
+        {
+            eprint!("whenever: {}\n\n{}", message, usage());
+            process::exit(2);
+        }
+    };
+    let source = match Source::read(&options.path)
+    {
+        Ok(source) => source,
+        Err(error) =>
+        {
+            eprintln!("whenever: cannot read {}: {}", options.path, error);
+            process::exit(1);
+        }
+    };
+
+    let success = match options.command
+    {
+        Command::Check => check(&source, &options),
+        Command::Dot => dot(&source, &options),
+        Command::Fmt => fmt(&source, &options),
+        Command::Run => run(&source, &options)
+    };
+    // Output is not flushed by `process::exit`
+    let _ = io::stdout().flush();
+    process::exit(if success { 0 } else { 1 });
+}
@@ -0,0 +1,217 @@
+use crate::error::{ErrorKind, ParseError};
+use crate::interp::{RuntimeError, RuntimeErrorKind};
+use crate::lexer;
+use crate::num;
+use crate::span::{self, Span};
+
+/// How diagnostics are printed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Style
+{
+    /// Plain text, for logs and files.
+    Plain,
+    /// Text colored with ANSI escape codes, for terminals.
+    Ansi
+}
+
+/// An error message attached to a part of the source, rendered like rustc
+/// does.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Diagnostic
+{
+    pub message: String,
+    /// Part of the source the error comes from, if known.
+    pub span: Option<Span>,
+    pub help: Option<String>
+}
+
+impl From<&ParseError> for Diagnostic
+{
+    fn from(error: &ParseError) -> Diagnostic
+    {
+        let help = match error.kind
+        {
+            ErrorKind::UnknownKeyword =>
+                lexer::suggest_keyword(&error.found).map(|keyword| {
+                    format!("keywords are case sensitive: did you mean `{}`?",
+                            keyword)
+                }),
+            ErrorKind::UnterminatedString =>
+                Some(String::from("strings end with a `\"`")),
+            ErrorKind::UnterminatedComment =>
+                Some(String::from("block comments end with a `*/`")),
+            ErrorKind::InvalidEscape =>
+                Some(String::from("known escape sequences are `\\\"`, \
+                                   `\\\\`, `\\n`, `\\t` and `\\u{...}`")),
+            ErrorKind::Overflow => num::OVERFLOW_HELP.map(String::from),
+            _ => None
+        };
+        Diagnostic { message: error.message(), span: Some(error.span), help }
+    }
+}
+
+impl From<&RuntimeError> for Diagnostic
+{
+    fn from(error: &RuntimeError) -> Diagnostic
+    {
+        let help = match error.kind
+        {
+            RuntimeErrorKind::Overflow => num::OVERFLOW_HELP.map(String::from),
+            _ => None
+        };
+        Diagnostic { message: error.message.clone(), span: error.span, help }
+    }
+}
+
+const BOLD: &str = "\x1b[1m";
+const RED: &str = "\x1b[1;31m";
+const BLUE: &str = "\x1b[1;34m";
+const CYAN: &str = "\x1b[1;36m";
+const RESET: &str = "\x1b[0m";
+
+impl Diagnostic
+{
+    /// Returns the diagnostic, with the line of `src` it points to and the
+    /// span underlined.
+    ///
+    /// `path` is the name of the file `src` comes from, if any.
+    pub fn render(&self, src: &str, path: Option<&str>, style: Style)
+        -> String
+    {
+        let paint = |color: &str, text: &str| match style
+        {
+            Style::Plain => String::from(text),
+            Style::Ansi => format!("{}{}{}", color, text, RESET)
+        };
+
+        let mut res = format!("{}{}\n", paint(RED, "error"),
+                              paint(BOLD, &format!(": {}", self.message)));
+        let span = match self.span
+        {
+            Some(span) => span,
+            None =>
+            {
+                if let Some(help) = &self.help
+                {
+                    res += &format!(" {} {}: {}\n", paint(BLUE, "="),
+                                    paint(CYAN, "help"), help);
+                }
+                return res;
+            }
+        };
+
+        let (line, column) = span::line_column(src, span.start);
+        let linestart = src[..span.start].rfind('\n')
+            .map_or(0, |pos| pos + 1);
+        let lineend = src[linestart..].find('\n')
+            .map_or(src.len(), |pos| linestart + pos);
+        let text = src[linestart..lineend].trim_end_matches('\r');
+
+        // Tabs are expanded so that carets line up with the text
+        let expand = |s: &str| s.replace('\t', "    ");
+        let before = expand(&src[linestart..span.start]);
+        let underlined = expand(&src[span.start..span.end.min(lineend)]);
+        let width = underlined.chars().count().max(1);
+
+        let lineno = line.to_string();
+        let gutter = " ".repeat(lineno.len());
+        let location = match path
+        {
+            Some(path) => format!("{}:{}:{}", path, line, column),
+            None => format!("{}:{}", line, column)
+        };
+
+        res += &format!("{}{} {}\n", gutter, paint(BLUE, "-->"), location);
+        res += &format!("{} {}\n", gutter, paint(BLUE, "|"));
+        res += &format!("{} {} {}\n", paint(BLUE, &lineno), paint(BLUE, "|"),
+                        expand(text));
+        res += &format!("{} {} {}{}\n", gutter, paint(BLUE, "|"),
+                        " ".repeat(before.chars().count()),
+                        paint(RED, &"^".repeat(width)));
+        if let Some(help) = &self.help
+        {
+            res += &format!("{} {} {}: {}\n", gutter, paint(BLUE, "="),
+                            paint(CYAN, "help"), help);
+        }
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_plain_check()
+    {
+        let input = "1 2;\n2 Again (1) 3;\n";
+        let error = crate::parse_program(input).err().unwrap();
+
+        let expected = "\
+error: unknown keyword `Again`
+ --> prog.wnvr:2:3
+  |
+2 | 2 Again (1) 3;
+  |   ^^^^^
+  = help: keywords are case sensitive: did you mean `again`?
+";
+        let diagnostic = Diagnostic::from(&error);
+        assert_eq!(diagnostic.render(input, Some("prog.wnvr"), Style::Plain),
+                   expected);
+    }
+
+    #[test]
+    fn render_end_of_input_check()
+    {
+        let input = "1\tdefer (1";
+        let error = crate::parse_line(input).err().unwrap();
+
+        let expected = "\
+error: expected `)`, found end of input
+ --> 1:11
+  |
+1 | 1    defer (1
+  |              ^
+";
+        let diagnostic = Diagnostic::from(&error);
+        assert_eq!(diagnostic.render(input, None, Style::Plain), expected);
+    }
+
+    #[test]
+    #[cfg(not(feature = "bigint"))]
+    fn render_runtime_check()
+    {
+        let input = "1 print(9223372036854775807\n        + N(1));\n";
+        let program = crate::parse_program(input).unwrap();
+        let mut io = crate::io::Memory::new("");
+        let error = crate::interp::run(&program, &mut io).err().unwrap();
+
+        let expected = "\
+error: attempt to add with overflow
+ --> 1:9
+  |
+1 | 1 print(9223372036854775807
+  |         ^^^^^^^^^^^^^^^^^^^
+  = help: numbers are 64-bit, the `bigint` feature makes them unbounded
+";
+        let diagnostic = Diagnostic::from(&error);
+        assert_eq!(diagnostic.render(input, None, Style::Plain), expected);
+
+        let diagnostic = Diagnostic { span: None, help: None, ..diagnostic };
+        assert_eq!(diagnostic.render(input, None, Style::Plain),
+                   "error: attempt to add with overflow\n");
+    }
+
+    #[test]
+    fn render_ansi_check()
+    {
+        let input = "1 print(\"a);";
+        let error = crate::parse_line(input).err().unwrap();
+
+        let rendered = Diagnostic::from(&error).render(input, None,
+                                                       Style::Ansi);
+        assert!(rendered.starts_with("\x1b[1;31merror\x1b[0m"));
+        assert!(rendered.contains("\x1b[1;31m^^^^\x1b[0m"));
+        assert!(rendered.contains("\x1b[1;36mhelp\x1b[0m"));
+    }
+}
@@ -0,0 +1,125 @@
+use std::error::Error;
+use std::fmt;
+
+use crate::span::{self, Span};
+
+/// The reason why parsing failed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorKind
+{
+    /// A token was found where it was not expected.
+    UnexpectedToken,
+    /// End of input was reached inside a string.
+    UnterminatedString,
+    /// A backslash in a string does not start a known escape sequence.
+    InvalidEscape,
+    /// End of input was reached inside a `/*` comment.
+    UnterminatedComment,
+    /// A number contains a digit that is not valid in its base.
+    InvalidDigit,
+    /// A word is not a keyword (reminder: keywords are case sensitive).
+    UnknownKeyword,
+    /// A character does not start any token.
+    UnknownToken,
+    /// A number literal does not fit in a number.
+    Overflow,
+    /// A complete line was read, but the input goes on.
+    TrailingInput,
+    /// Nodes are nested deeper than the maximum depth of the cursor.
+    NestingTooDeep
+}
+
+/// An error found while tokenizing or parsing.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseError
+{
+    pub kind: ErrorKind,
+    /// Bytes of the input where the error happened.
+    pub span: Span,
+    /// 1-based line of `span.start`.
+    pub line: usize,
+    /// 1-based column of `span.start`, in characters.
+    pub column: usize,
+    /// Text of the input at `span`.
+    pub found: String,
+    /// Descriptions of the tokens that would have been accepted instead.
+    pub expected: Vec<&'static str>
+}
+
+impl ParseError
+{
+    /// Returns an error of kind `kind` at `span` of `src`.
+    pub fn new(kind: ErrorKind, src: &str, span: Span) -> ParseError
+    {
+        let (line, column) = span::line_column(src, span.start);
+        ParseError {
+            kind,
+            span,
+            line,
+            column,
+            found: String::from(&src[span.range()]),
+            expected: Vec::new()
+        }
+    }
+
+    /// Returns an `ErrorKind::UnexpectedToken` error at `span` of `src`.
+    pub fn unexpected(src: &str, span: Span, expected: &[&'static str])
+        -> ParseError
+    {
+        let mut error = ParseError::new(ErrorKind::UnexpectedToken, src, span);
+        error.expected = expected.to_vec();
+        error
+    }
+
+    /// Returns a description of the error, without its position.
+    pub fn message(&self) -> String
+    {
+        let found = if self.found.is_empty()
+        {
+            String::from("end of input")
+        }
+        else
+        {
+            format!("`{}`", self.found)
+        };
+
+        match self.kind
+        {
+            ErrorKind::UnexpectedToken | ErrorKind::TrailingInput =>
+            {
+                let expected = match self.expected.split_last()
+                {
+                    None => String::from("something else"),
+                    Some((last, [])) => String::from(*last),
+                    Some((last, rest)) =>
+                        format!("{} or {}", rest.join(", "), last)
+                };
+                format!("expected {}, found {}", expected, found)
+            }
+            ErrorKind::UnterminatedString =>
+                String::from("end of input while reading string"),
+            ErrorKind::UnterminatedComment =>
+                String::from("end of input while reading comment"),
+            ErrorKind::InvalidEscape =>
+                format!("invalid escape sequence {} in string", found),
+            ErrorKind::InvalidDigit =>
+                format!("invalid digit {} in number", found),
+            ErrorKind::UnknownKeyword =>
+                format!("unknown keyword {}", found),
+            ErrorKind::UnknownToken => format!("unknown token {}", found),
+            ErrorKind::Overflow => format!("number {} is too large", found),
+            ErrorKind::NestingTooDeep =>
+                format!("nesting too deep at {}", found)
+        }
+    }
+}
+
+impl fmt::Display for ParseError
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message())
+    }
+}
+
+impl Error for ParseError {}
@@ -0,0 +1,721 @@
+// Formatting of the AST back into source
+//
+// The formatter prints canonical source from the AST rather than from the
+// input: numbers and strings keep their literal text, but spacing is made
+// consistent and parentheses are only kept where precedence and associativity
+// require them. Formatting the output again gives the same text, and parsing
+// it gives the same tree, parentheses aside.
+//
+// Lines too wide for the style are broken before each nested statement, then
+// after the commas of their list of line operations.
+
+use crate::ast::{self, Graph};
+use crate::num::Int;
+
+/// How formatted source is laid out.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Style
+{
+    /// Number of characters after which a line is wrapped, if possible.
+    pub width: usize,
+    /// Number of spaces before the continuation of a wrapped line.
+    pub indent: usize,
+    /// Whether binary operators are surrounded by spaces.
+    pub spaced_operators: bool,
+    /// Whether lines are sorted by number, instead of kept in source order.
+    pub sort_lines: bool
+}
+
+impl Default for Style
+{
+    fn default() -> Style
+    {
+        Style {
+            width: 80,
+            indent: 4,
+            spaced_operators: true,
+            sort_lines: false
+        }
+    }
+}
+
+// Precedence of numbers, from loosest to tightest. An operand whose precedence
+// is lower than what its position requires is put between parentheses.
+const SUM: u8 = 1;
+const PRODUCT: u8 = 2;
+const UNARY: u8 = 3;
+const ATOM: u8 = 4;
+
+// Precedence of booleans
+const OR: u8 = 1;
+const AND: u8 = 2;
+const COMPARISON: u8 = 3;
+const NEGATION: u8 = 4;
+
+fn operator(op: &str, style: &Style) -> String
+{
+    if style.spaced_operators
+    {
+        format!(" {} ", op)
+    }
+    else
+    {
+        String::from(op)
+    }
+}
+
+fn parenthesize(text: String, level: u8, min: u8) -> String
+{
+    if level < min
+    {
+        format!("({})", text)
+    }
+    else
+    {
+        text
+    }
+}
+
+fn binop_level(binop: &ast::BinOpNumber) -> u8
+{
+    match binop.op.alt
+    {
+        ast::BinMathOpKind::MathOp(_) => PRODUCT,
+        _ => SUM
+    }
+}
+
+fn number_level(number: &ast::Number) -> u8
+{
+    match &number.alt
+    {
+        ast::NumberKind::Absolute(_) | ast::NumberKind::StringToNum(_) => ATOM,
+        ast::NumberKind::UnOp(_) => UNARY,
+        ast::NumberKind::BinOp(binop) => binop_level(binop),
+        ast::NumberKind::Parens(parens) => number_level(&parens.num)
+    }
+}
+
+fn abs_number(number: &ast::AbsNumber, style: &Style) -> String
+{
+    match &number.alt
+    {
+        ast::AbsNumberKind::Number(number) => String::from(number.tok),
+        ast::AbsNumberKind::N(n) => format!("N({})", format_number(&n.num,
+                                                                   SUM,
+                                                                   style)),
+        ast::AbsNumberKind::Read(_) => String::from("read()")
+    }
+}
+
+fn format_number(number: &ast::Number, min: u8, style: &Style) -> String
+{
+    let level = number_level(number);
+    let text = match &number.alt
+    {
+        ast::NumberKind::Absolute(absolute) => abs_number(&absolute.num,
+                                                          style),
+        ast::NumberKind::UnOp(unop) =>
+        {
+            let op = match unop.op.alt
+            {
+                ast::UnMathOpKind::Plus(_) => "+",
+                ast::UnMathOpKind::Minus(_) => "-"
+            };
+            format!("{}{}", op, format_number(&unop.num, UNARY, style))
+        },
+        ast::NumberKind::BinOp(binop) =>
+        {
+            // Chains are nested to the left as deep as they are long, so they
+            // are formatted from their first number rather than recursively
+            let mut links = vec![&**binop];
+            while let ast::NumberKind::BinOp(binop) =
+                &links[links.len() - 1].num1.alt
+            {
+                if binop_level(binop) < binop_level(links[links.len() - 1])
+                {
+                    break;
+                }
+                links.push(binop);
+            }
+            let first = links[links.len() - 1];
+            let mut text = format_number(&first.num1, binop_level(first),
+                                         style);
+            for binop in links.into_iter().rev()
+            {
+                let op = match &binop.op.alt
+                {
+                    ast::BinMathOpKind::Plus(_) => "+",
+                    ast::BinMathOpKind::Minus(_) => "-",
+                    ast::BinMathOpKind::MathOp(op) => match op.val
+                    {
+                        ast::MulOp::Mul => "*",
+                        ast::MulOp::Div => "/"
+                    }
+                };
+                text += operator(op, style).as_str();
+                text += format_number(&binop.num2, binop_level(binop) + 1,
+                                      style).as_str();
+            }
+            text
+        },
+        ast::NumberKind::Parens(parens) =>
+            return format_number(&parens.num, min, style),
+        ast::NumberKind::StringToNum(conversion) =>
+            string_operand(&conversion.string, style)
+    };
+    parenthesize(text, level, min)
+}
+
+fn binop_boolean_level(binop: &ast::BinOpBoolean) -> u8
+{
+    match binop.op.val
+    {
+        ast::BoolOp::Or => OR,
+        ast::BoolOp::And => AND
+    }
+}
+
+fn boolean_level(boolean: &ast::Boolean) -> u8
+{
+    match &boolean.alt
+    {
+        ast::BooleanKind::UnOp(_) => NEGATION,
+        ast::BooleanKind::BinOp(binop) => binop_boolean_level(binop),
+        ast::BooleanKind::BinOpNum(_) => COMPARISON,
+        ast::BooleanKind::Parens(parens) => boolean_level(&parens.boolean),
+        // `!1+2` is valid, but reads better as `!(1+2)`
+        ast::BooleanKind::NumToBool(conversion) =>
+            if number_level(&conversion.num) >= UNARY
+            {
+                NEGATION
+            }
+            else
+            {
+                COMPARISON
+            }
+    }
+}
+
+fn format_boolean(boolean: &ast::Boolean, min: u8, style: &Style) -> String
+{
+    let level = boolean_level(boolean);
+    let text = match &boolean.alt
+    {
+        ast::BooleanKind::UnOp(unop) =>
+            format!("!{}", format_boolean(&unop.boolean, NEGATION, style)),
+        ast::BooleanKind::BinOp(binop) =>
+        {
+            // Chains are nested to the left, like those of numbers
+            let mut links = vec![&**binop];
+            while let ast::BooleanKind::BinOp(binop) =
+                &links[links.len() - 1].boolean1.alt
+            {
+                let last = links[links.len() - 1];
+                if binop_boolean_level(binop) < binop_boolean_level(last)
+                {
+                    break;
+                }
+                links.push(binop);
+            }
+            let first = links[links.len() - 1];
+            let mut text = format_boolean(&first.boolean1,
+                                          binop_boolean_level(first), style);
+            for binop in links.into_iter().rev()
+            {
+                let op = match binop.op.val
+                {
+                    ast::BoolOp::Or => "||",
+                    ast::BoolOp::And => "&&"
+                };
+                text += operator(op, style).as_str();
+                text += format_boolean(&binop.boolean2,
+                                       binop_boolean_level(binop) + 1,
+                                       style).as_str();
+            }
+            text
+        },
+        ast::BooleanKind::BinOpNum(comparison) =>
+        {
+            let op = match comparison.op.val
+            {
+                ast::CmpOp::Lt => "<",
+                ast::CmpOp::Le => "<=",
+                ast::CmpOp::Gt => ">",
+                ast::CmpOp::Ge => ">=",
+                ast::CmpOp::Eq => "==",
+                ast::CmpOp::Ne => "!="
+            };
+            format_number(&comparison.num1, SUM, style)
+                + operator(op, style).as_str()
+                + format_number(&comparison.num2, SUM, style).as_str()
+        },
+        ast::BooleanKind::Parens(parens) =>
+            return format_boolean(&parens.boolean, min, style),
+        ast::BooleanKind::NumToBool(conversion) =>
+            format_number(&conversion.num, SUM, style)
+    };
+    parenthesize(text, level, min)
+}
+
+// Whether a number may be printed starting with a string
+fn starts_with_string(mut number: &ast::Number) -> bool
+{
+    loop
+    {
+        number = match &number.alt
+        {
+            ast::NumberKind::StringToNum(_) => return true,
+            ast::NumberKind::BinOp(binop) => &binop.num1,
+            ast::NumberKind::Parens(parens) => &parens.num,
+            _ => return false
+        };
+    }
+}
+
+// Formats a number converted to a string, which would be read as a string if
+// it started with one
+fn number_to_string(number: &ast::Number, min: u8, style: &Style) -> String
+{
+    if starts_with_string(number)
+    {
+        format!("({})", format_number(number, SUM, style))
+    }
+    else
+    {
+        format_number(number, min, style)
+    }
+}
+
+// Whether a number would be printed with a `+` outside of parentheses
+fn has_plus(mut number: &ast::Number) -> bool
+{
+    loop
+    {
+        number = match &number.alt
+        {
+            ast::NumberKind::BinOp(binop) => match binop.op.alt
+            {
+                ast::BinMathOpKind::Plus(_) => return true,
+                ast::BinMathOpKind::Minus(_) => &binop.num1,
+                ast::BinMathOpKind::MathOp(_) => return false
+            },
+            ast::NumberKind::Parens(parens) => &parens.num,
+            _ => return false
+        };
+    }
+}
+
+// Formats an operand of a concatenation other than the first one
+fn string_operand(string: &ast::String_, style: &Style) -> String
+{
+    match &string.alt
+    {
+        ast::StringKind::String(string) => String::from(string.tok),
+        ast::StringKind::U(u) => format!("U({})", abs_number(&u.num, style)),
+        // Concatenations are left associative, a number on the right of one
+        // is read up to the next `+`
+        ast::StringKind::NumToString(conversion)
+            if has_plus(&conversion.num) =>
+            format!("({})", format_number(&conversion.num, SUM, style)),
+        ast::StringKind::NumToString(conversion) =>
+            number_to_string(&conversion.num, SUM, style),
+        ast::StringKind::Concat(_) => format_string(string, style)
+    }
+}
+
+fn format_string(string: &ast::String_, style: &Style) -> String
+{
+    match &string.alt
+    {
+        ast::StringKind::Concat(concat) =>
+        {
+            // Concatenations are nested to the left, like chains of numbers
+            let mut links = vec![&**concat];
+            while let ast::StringKind::Concat(concat) =
+                &links[links.len() - 1].str1.alt
+            {
+                links.push(concat);
+            }
+            let mut text = format_string(&links[links.len() - 1].str1, style);
+            for concat in links.into_iter().rev()
+            {
+                text += operator("+", style).as_str();
+                text += string_operand(&concat.str2, style).as_str();
+            }
+            text
+        },
+        // A number first in a string is read as a whole
+        ast::StringKind::NumToString(conversion) =>
+            number_to_string(&conversion.num, SUM, style),
+        _ => string_operand(string, style)
+    }
+}
+
+fn single_lineop(lineop: &ast::SingleLineOp, style: &Style) -> String
+{
+    match &lineop.alt
+    {
+        ast::SingleLineOpKind::NumToLineOp(conversion) =>
+            format_number(&conversion.num, SUM, style),
+        ast::SingleLineOpKind::Count(count) =>
+            format!("{}#{}", format_number(&count.line, SUM, style),
+                    format_number(&count.count, SUM, style))
+    }
+}
+
+fn lineops(mut lineops: &ast::LineOps, style: &Style) -> Vec<String>
+{
+    let mut res = Vec::new();
+    loop
+    {
+        match &lineops.alt
+        {
+            ast::LineOpsKind::LineOp(lineop) =>
+            {
+                res.push(single_lineop(&lineop.slo, style));
+                return res;
+            },
+            ast::LineOpsKind::List(list) =>
+            {
+                res.push(single_lineop(&list.lineop.slo, style));
+                lineops = &list.list;
+            }
+        }
+    }
+}
+
+// Splits a statement into the heads of its nested statements, such as
+// `again (1)`, and the items of the innermost one, which are separated by
+// commas.
+fn statement(mut statement: &ast::Statement, style: &Style)
+    -> (Vec<String>, Vec<String>)
+{
+    let mut heads = Vec::new();
+    loop
+    {
+        let (keyword, boolean, next) = match &statement.alt
+        {
+            ast::StatementKind::LineOperations(operations) =>
+                return (heads, lineops(&operations.lineops, style)),
+            ast::StatementKind::Print(print) =>
+            {
+                let text = format!("print({})",
+                                   format_string(&print.string, style));
+                return (heads, vec![text]);
+            },
+            ast::StatementKind::Again(again) =>
+                ("again", &again.boolean, &again.statement),
+            ast::StatementKind::Defer(defer) =>
+                ("defer", &defer.boolean, &defer.statement),
+            ast::StatementKind::Forget(forget) =>
+                ("forget", &forget.boolean, &forget.statement)
+        };
+        heads.push(format!("{} ({})", keyword,
+                           format_boolean(boolean, OR, style)));
+        statement = next;
+    }
+}
+
+fn width(text: &str) -> usize
+{
+    text.chars().count()
+}
+
+/// Formats a line, without a trailing newline.
+///
+/// The result holds several physical lines when the line does not fit in
+/// `style.width`.
+pub fn format_line(line: &ast::Line, style: &Style) -> String
+{
+    let (heads, mut items) = statement(&line.stmt, style);
+    if let Some(last) = items.last_mut()
+    {
+        last.push(';');
+    }
+
+    let mut flat = String::from(line.num.tok);
+    for head in &heads
+    {
+        flat += " ";
+        flat += head;
+    }
+    flat += " ";
+    flat += items.join(", ").as_str();
+    if width(&flat) <= style.width
+    {
+        return flat;
+    }
+
+    let indent = " ".repeat(style.indent);
+    let mut res = String::new();
+    let mut current = String::from(line.num.tok) + " ";
+    for head in &heads
+    {
+        current += head;
+        res += current.as_str();
+        res += "\n";
+        current = indent.clone();
+    }
+    let mut empty = true;
+    for (i, item) in items.iter().enumerate()
+    {
+        // Items but the last one are followed by a comma
+        let needed = width(item) + if i + 1 < items.len() { 1 } else { 0 };
+        if !empty && width(&current) + 2 + needed > style.width
+        {
+            res += current.as_str();
+            res += ",\n";
+            current = indent.clone();
+            empty = true;
+        }
+        if !empty
+        {
+            current += ", ";
+        }
+        current += item;
+        empty = false;
+    }
+    res + current.as_str()
+}
+
+// A line of the output along with the comments around it
+struct Block
+{
+    // Comments on the physical lines before it
+    leading: Vec<String>,
+    text: String,
+    // Comments on the same physical line as the end of the line
+    trailing: Vec<String>,
+    // Whether an empty line precedes the block in the source
+    spaced: bool,
+    // Number of the line, or of the last one before it
+    key: Option<Int>
+}
+
+fn push_comments(res: &mut String, comments: &[String])
+{
+    for comment in comments
+    {
+        *res += comment.as_str();
+        *res += "\n";
+    }
+}
+
+/// Formats a whole program, one line after the other.
+///
+/// Comments are kept, either before the line that follows them or at the end
+/// of the line they are on. Empty lines between lines are kept, but several in
+/// a row are collapsed into one. Lines that could not be parsed are printed
+/// as they are.
+pub fn format_program(program: &ast::Program, style: &Style) -> String
+{
+    let src = program.src;
+    let mut blocks: Vec<Block> = Vec::new();
+    let mut prev_end = program.span.start;
+    let mut key = None;
+    for line in &program.lines
+    {
+        let span = line.span();
+        let text = match line
+        {
+            ast::ProgramLine::Line(line) =>
+            {
+                key = Some(line.num.val.clone());
+                format_line(line, style)
+            },
+            ast::ProgramLine::Error(error) =>
+                String::from(error.get_str())
+        };
+        let spaced = !blocks.is_empty()
+            && src[prev_end..span.start].matches('\n').count() > 1;
+        blocks.push(Block {
+            leading: Vec::new(),
+            text,
+            trailing: Vec::new(),
+            spaced,
+            key: key.clone()
+        });
+        prev_end = span.end;
+    }
+
+    let mut end = Vec::new();
+    for comment in &program.comments
+    {
+        let next = comment.line.unwrap_or(program.lines.len());
+        let text = String::from(comment.tok);
+        if next > 0
+        {
+            let prev = program.lines[next - 1].span();
+            if comment.span.start < prev.end
+                || !src[prev.end..comment.span.start].contains('\n')
+            {
+                blocks[next - 1].trailing.push(text);
+                continue;
+            }
+        }
+        match blocks.get_mut(next)
+        {
+            Some(block) => block.leading.push(text),
+            None => end.push(text)
+        }
+    }
+
+    if style.sort_lines
+    {
+        blocks.sort_by(|block1, block2| block1.key.cmp(&block2.key));
+        if let Some(block) = blocks.first_mut()
+        {
+            block.spaced = false;
+        }
+    }
+
+    let mut res = String::new();
+    for block in &blocks
+    {
+        if block.spaced
+        {
+            res += "\n";
+        }
+        push_comments(&mut res, &block.leading);
+        res += block.text.as_str();
+        let mut after_line_comment = false;
+        for comment in &block.trailing
+        {
+            res += if after_line_comment { "\n" } else { " " };
+            res += comment.as_str();
+            after_line_comment = comment.starts_with("//");
+        }
+        res += "\n";
+    }
+    push_comments(&mut res, &end);
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Checks that a line is formatted as expected, and that formatting is
+    // idempotent
+    macro_rules! check_format
+    {
+        ($input: expr, $expected: expr) =>
+        {
+            check_format!($input, $expected, &Style::default())
+        };
+        ($input: expr, $expected: expr, $style: expr) =>
+        {
+            {
+                let line = crate::parse_line($input).unwrap();
+                let actual = format_line(&line, $style);
+                assert_eq!(actual, $expected);
+                let line = crate::parse_line(&actual).unwrap();
+                assert_eq!(format_line(&line, $style), $expected);
+            }
+        };
+    }
+
+    #[test]
+    fn spacing_check()
+    {
+        check_format!("1   again(1)defer(3||N(1)<=N(2)||N(7)>99)2#N(1),3,7;",
+                      "1 again (1) defer (3 || N(1) <= N(2) || N(7) > 99) \
+                       2#N(1), 3, 7;");
+        check_format!("3 forget(!read ( )) print(N(1)+\"a\"+U(65));",
+                      "3 forget (!read()) print(N(1) + \"a\" + U(65));");
+        check_format!("0x10 - 0x1f ,+4;", "0x10 -0x1f, +4;");
+        let compact = Style { spaced_operators: false, ..Style::default() };
+        check_format!("1 defer (1 || 2 && N(3) != 4) 1 + 2 * 3;",
+                      "1 defer (1||2&&N(3)!=4) 1+2*3;",
+                      &compact);
+    }
+
+    #[test]
+    fn parentheses_check()
+    {
+        check_format!("1 ((1)+(2*3));", "1 1 + 2 * 3;");
+        check_format!("1 (1+2)*3;", "1 (1 + 2) * 3;");
+        check_format!("1 (1-2)-(3-4);", "1 1 - 2 - (3 - 4);");
+        check_format!("1 (8/4)/(2*1);", "1 8 / 4 / (2 * 1);");
+        check_format!("1 -(1+2)*-(-3);", "1 -(1 + 2) * --3;");
+        check_format!("1 N((1+2));", "1 N(1 + 2);");
+        check_format!("1 print(\"a\"+(1+2)+3);",
+                      "1 print(\"a\" + (1 + 2) + 3);");
+        check_format!("1 print(\"a\"+(1*2));", "1 print(\"a\" + 1 * 2);");
+        check_format!("1 print(\"a\"+(1-2)-3);", "1 print(\"a\" + 1 - 2 - 3);");
+        check_format!("1 print(\"a\"+((1+2)-3));",
+                      "1 print(\"a\" + (1 + 2 - 3));");
+        check_format!("1 print(\"a\"+1-(2+3));",
+                      "1 print(\"a\" + 1 - (2 + 3));");
+        // A string first would not be read as part of a number
+        check_format!("1 print((\"1\")*2+(\"3\"));",
+                      "1 print((\"1\" * 2 + \"3\"));");
+        check_format!("1 print((\"a\"+(1+2))+3);",
+                      "1 print((\"a\" + (1 + 2) + 3));");
+        check_format!("1 print(\"a\"+(\"1\"/2));",
+                      "1 print(\"a\" + (\"1\" / 2));");
+        check_format!("1 defer ((1||2)&&(3)) 4;", "1 defer ((1 || 2) && 3) 4;");
+        check_format!("1 defer ((1&&2)||(3||4)) 4;",
+                      "1 defer (1 && 2 || (3 || 4)) 4;");
+        check_format!("1 defer (!(N(1)==2)&&!(1+2)&&!(-1)) 4;",
+                      "1 defer (!(N(1) == 2) && !(1 + 2) && !-1) 4;");
+        check_format!("1 defer (((N(1)+1)*2)>2||(3)) 4;",
+                      "1 defer ((N(1) + 1) * 2 > 2 || 3) 4;");
+    }
+
+    #[test]
+    fn width_check()
+    {
+        let style = Style { width: 20, ..Style::default() };
+        check_format!("1 again (N(1) > 2) defer (N(3) > 4) 1, 2;",
+                      "1 again (N(1) > 2)\n    defer (N(3) > 4)\n    1, 2;",
+                      &style);
+        check_format!("1 100, 200, 300, 400, 500, 600;",
+                      "1 100, 200, 300,\n    400, 500, 600;",
+                      &style);
+        let style = Style { width: 10, indent: 2, ..Style::default() };
+        check_format!("1 print(\"too long to fit\");",
+                      "1 print(\"too long to fit\");",
+                      &style);
+        check_format!("10 100, 2000000000;", "10 100,\n  2000000000;", &style);
+    }
+
+    #[test]
+    fn program_check()
+    {
+        let input = "// head\n\
+                     2 1; // two\n\n\n\
+                     /* one */ 1 print(\"a\") /* in */ ;\n\
+                     3 4 // first\n\
+                     ; // second\n\
+                     // tail\n";
+        let program = crate::parse_program(input).unwrap();
+        let expected = "// head\n\
+                        2 1; // two\n\n\
+                        /* one */\n\
+                        1 print(\"a\"); /* in */\n\
+                        3 4; // first\n\
+                        // second\n\
+                        // tail\n";
+        assert_eq!(format_program(&program, &Style::default()), expected);
+
+        let style = Style { sort_lines: true, ..Style::default() };
+        let expected = "/* one */\n\
+                        1 print(\"a\"); /* in */\n\
+                        // head\n\
+                        2 1; // two\n\
+                        3 4; // first\n\
+                        // second\n\
+                        // tail\n";
+        assert_eq!(format_program(&program, &style), expected);
+
+        // Lines that could not be parsed stay after the line before them
+        let input = "2 1;\n2 (;\n1 1;\n";
+        let (program, _) = crate::parse_program_recovering(input);
+        let expected = "1 1;\n2 1;\n2 (;\n";
+        assert_eq!(format_program(&program, &style), expected);
+        assert_eq!(format_program(&crate::parse_program("").unwrap(), &style),
+                   "");
+    }
+}
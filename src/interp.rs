@@ -0,0 +1,805 @@
+// Semantics:
+// The program keeps a to-do list of line numbers, where a line may appear
+// several times. Initially, every line of the program appears once. At each
+// step, a copy of a line is picked at random in the list and executed:
+// * `defer (b) s`: if `b` holds, nothing happens and the line stays in the
+//   list, else `s` is executed
+// * `again (b) s`: `s` is executed, and if `b` held, the line stays in the
+//   list
+// * `forget (b) s`: if `b` holds, `s` is not executed and every copy of the
+//   line is removed from the list, else `s` is executed
+// * `print(s)`: `s` is printed, followed by a newline
+// * `n`: a copy of line `n` is added to the list, or removed if `n` is
+//   negative
+// * `n#m`: `m` copies of line `n` are added to the list, or removed if `n` is
+//   negative (lines cannot appear a negative number of times)
+// Then, unless the line was deferred or kept by `again`, one copy of it is
+// removed from the list. The program ends when the list is empty.
+//
+// `N(n)` is the number of times line `n` appears in the list. A number `n`
+// used as a boolean holds if line `n` appears in the list, that is if `N(n)`
+// is not 0. `read()` reads a line of input, and is the number on it, or the
+// Unicode value of its first character. `U(n)` is the character of Unicode
+// value `n`. `+` concatenates when either side is a string, and strings are
+// converted back to numbers where a number is needed.
+//
+// Numbers are `num::Int`s. Line numbers are taken without their sign where a
+// line is expected, and what happens when a computation does not fit in an
+// `Int` depends on the `num::OverflowMode` of the interpreter.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::ast::{self, Graph};
+use crate::io::Io;
+use crate::num::{Int, OverflowMode};
+use crate::scheduler::{self, Scheduler};
+use crate::span::Span;
+
+/// The reason why execution failed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RuntimeErrorKind
+{
+    /// The program contains lines that could not be parsed.
+    SyntaxError,
+    /// Two lines of the program have the same number.
+    DuplicateLine,
+    /// A line that is not in the program was referenced.
+    NoSuchLine,
+    /// A string that is not a number was used as a number.
+    NotANumber,
+    /// A number is not the Unicode value of a character.
+    InvalidCharacter,
+    DivisionByZero,
+    /// A computation does not fit in a `num::Int`.
+    Overflow,
+    /// Reading input or writing output failed.
+    Io,
+    /// The scheduler did not pick a line in the to-do list.
+    Schedule
+}
+
+/// An error found while executing a program.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RuntimeError
+{
+    pub kind: RuntimeErrorKind,
+    /// Number of the line being executed, if any.
+    pub line: Option<usize>,
+    /// Part of the source the error comes from, if any.
+    pub span: Option<Span>,
+    pub message: String
+}
+
+impl RuntimeError
+{
+    fn new(kind: RuntimeErrorKind, message: String) -> RuntimeError
+    {
+        RuntimeError { kind, line: None, span: None, message }
+    }
+}
+
+impl fmt::Display for RuntimeError
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        match self.line
+        {
+            Some(line) => write!(f, "line {}: {}", line, self.message),
+            None => write!(f, "{}", self.message)
+        }
+    }
+}
+
+impl std::error::Error for RuntimeError {}
+
+/// A value computed by the program.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Value
+{
+    Number(Int),
+    String(String)
+}
+
+impl Value
+{
+    /// Returns the value as a number, converting strings if possible.
+    pub fn to_number(&self) -> Result<Int, RuntimeError>
+    {
+        match self
+        {
+            Value::Number(num) => Ok(num.clone()),
+            Value::String(string) =>
+                Int::parse(string.trim()).ok_or_else(|| {
+                    RuntimeError::new(RuntimeErrorKind::NotANumber,
+                                      format!("\"{}\" is not a number",
+                                              string))
+                })
+        }
+    }
+}
+
+impl fmt::Display for Value
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        match self
+        {
+            Value::Number(num) => write!(f, "{}", num),
+            Value::String(string) => write!(f, "{}", string)
+        }
+    }
+}
+
+/// How the execution of a line ended.
+enum Outcome
+{
+    Executed,
+    Deferred,
+    Again,
+    Forgotten
+}
+
+/// Executes a program.
+pub struct Interpreter<'p, 'a>
+{
+    lines: BTreeMap<usize, &'p ast::Line<'a>>,
+    todo: BTreeMap<usize, Int>,
+    // The entries of `todo` given to the scheduler, empty when out of date
+    entries: Vec<(usize, Int)>,
+    // The counts before the current step of the lines it changed
+    before: BTreeMap<usize, Int>,
+    scheduler: Box<dyn Scheduler>,
+    mode: OverflowMode
+}
+
+impl<'p, 'a> Interpreter<'p, 'a>
+{
+    /// Returns an interpreter at the start of `program`, picking lines
+    /// randomly.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the program contains lines that could not be
+    /// parsed, or several lines with the same number.
+    pub fn new(program: &'p ast::Program<'a>)
+        -> Result<Interpreter<'p, 'a>, RuntimeError>
+    {
+        Interpreter::with_scheduler(program,
+                                    Box::new(scheduler::Random::from_time()))
+    }
+
+    /// Returns an interpreter at the start of `program`, picking lines
+    /// pseudo-randomly from `seed`.
+    pub fn with_seed(program: &'p ast::Program<'a>, seed: u64)
+        -> Result<Interpreter<'p, 'a>, RuntimeError>
+    {
+        Interpreter::with_scheduler(program,
+                                    Box::new(scheduler::Random::new(seed)))
+    }
+
+    /// Returns an interpreter at the start of `program`, picking lines with
+    /// `scheduler`.
+    pub fn with_scheduler(program: &'p ast::Program<'a>,
+                          scheduler: Box<dyn Scheduler>)
+        -> Result<Interpreter<'p, 'a>, RuntimeError>
+    {
+        let mut lines = BTreeMap::new();
+        for line in &program.lines
+        {
+            let line = match line
+            {
+                ast::ProgramLine::Line(line) => line,
+                ast::ProgramLine::Error(error) =>
+                {
+                    let mut res = RuntimeError::new(
+                        RuntimeErrorKind::SyntaxError,
+                        format!("cannot run invalid line `{}`",
+                                error.get_str()));
+                    res.span = Some(error.span());
+                    return Err(res);
+                }
+            };
+            let (kind, message) = match line.num.val.abs_usize()
+            {
+                Some(num) if lines.insert(num, line).is_none() => continue,
+                Some(num) => (RuntimeErrorKind::DuplicateLine,
+                              format!("line {} is defined twice", num)),
+                None => (RuntimeErrorKind::Overflow,
+                         format!("line number {} is too large",
+                                 line.num.val))
+            };
+            let mut res = RuntimeError::new(kind, message);
+            res.span = Some(line.num.span);
+            return Err(res);
+        }
+        let todo = lines.keys().map(|&num| (num, Int::from(1))).collect();
+
+        Ok(Interpreter {
+            lines,
+            todo,
+            entries: Vec::new(),
+            before: BTreeMap::new(),
+            scheduler,
+            mode: OverflowMode::default()
+        })
+    }
+
+    /// Sets what happens when a computation overflows, `Checked` by default.
+    pub fn set_overflow_mode(&mut self, mode: OverflowMode)
+    {
+        self.mode = mode;
+    }
+
+    /// Returns `N(line)`, the number of times `line` is in the to-do list.
+    pub fn count(&self, line: usize) -> Int
+    {
+        self.todo.get(&line).cloned().unwrap_or_default()
+    }
+
+    /// Returns `true` if the to-do list is empty.
+    pub fn is_done(&self) -> bool
+    {
+        self.todo.is_empty()
+    }
+
+    /// Executes one line picked from the to-do list.
+    ///
+    /// Returns the number of the line executed, or `None` if the to-do list
+    /// was empty.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the scheduler picks no line or a line that is not
+    /// in the to-do list, or if executing the line fails.
+    pub fn step(&mut self, io: &mut dyn Io)
+        -> Result<Option<usize>, RuntimeError>
+    {
+        if self.todo.is_empty()
+        {
+            return Ok(None);
+        }
+        if self.entries.is_empty()
+        {
+            self.entries.extend(self.todo.iter()
+                                .map(|(&line, count)| (line, count.clone())));
+        }
+        let num = self.scheduler.pick(&self.entries).ok_or_else(|| {
+            RuntimeError::new(RuntimeErrorKind::Schedule,
+                              String::from("no line was scheduled"))
+        })?;
+        if !self.todo.contains_key(&num)
+        {
+            return Err(RuntimeError::new(
+                RuntimeErrorKind::Schedule,
+                format!("line {} was scheduled but is not in the to-do list",
+                        num)));
+        }
+        let line = self.lines[&num];
+        self.before.clear();
+
+        let mut context = Context { interp: self, io };
+        let outcome = context.exec(&line.stmt).map_err(|mut error| {
+            error.line = Some(num);
+            error
+        })?;
+        match outcome
+        {
+            Outcome::Executed =>
+            {
+                // The line is in the list, removing it cannot overflow
+                self.add(num, &Int::from(-1));
+            }
+            Outcome::Forgotten =>
+            {
+                self.set(num, Int::default());
+            }
+            Outcome::Deferred | Outcome::Again => ()
+        }
+        let changed = self.before.iter()
+            .any(|(&line, count)| self.count(line) != *count);
+        self.scheduler.executed(num, changed);
+        Ok(Some(num))
+    }
+
+    /// Executes lines until the to-do list is empty.
+    pub fn run(&mut self, io: &mut dyn Io) -> Result<(), RuntimeError>
+    {
+        while self.step(io)?.is_some() {}
+        Ok(())
+    }
+
+    /// Adds `delta` copies of `line` to the to-do list, never going below 0.
+    ///
+    /// Returns `None` if the number of copies overflows.
+    fn add(&mut self, line: usize, delta: &Int) -> Option<()>
+    {
+        let count = self.mode.add(&self.count(line), delta)?;
+        self.set(line, count);
+        Some(())
+    }
+
+    /// Sets the number of copies of `line` in the to-do list, removing it if
+    /// `count` is not positive, and remembers the count it had before the
+    /// current step.
+    fn set(&mut self, line: usize, count: Int)
+    {
+        let old = if count.is_zero() || count.is_negative()
+        {
+            self.todo.remove(&line)
+        }
+        else
+        {
+            self.todo.insert(line, count)
+        };
+        match (old.is_some(), self.todo.get(&line))
+        {
+            (true, Some(count)) =>
+            {
+                let index = self.entries.binary_search_by_key(&line,
+                                                              |entry| entry.0);
+                if let Ok(index) = index
+                {
+                    self.entries[index].1 = count.clone();
+                }
+            }
+            (false, None) => (),
+            // The line left or entered the to-do list
+            _ => self.entries.clear()
+        }
+        self.before.entry(line).or_insert_with(|| old.unwrap_or_default());
+    }
+}
+
+/// Executes `program` until its to-do list is empty, picking lines randomly.
+pub fn run(program: &ast::Program, io: &mut dyn Io)
+    -> Result<(), RuntimeError>
+{
+    Interpreter::new(program)?.run(io)
+}
+
+/// What is needed to evaluate a line.
+struct Context<'i, 'p, 'a>
+{
+    interp: &'i mut Interpreter<'p, 'a>,
+    io: &'i mut dyn Io
+}
+
+impl<'i, 'p, 'a> Context<'i, 'p, 'a>
+{
+    /// Returns an error coming from `node`.
+    fn error(&self, kind: RuntimeErrorKind, message: String,
+             node: &dyn Graph<'a>) -> RuntimeError
+    {
+        self.locate(RuntimeError::new(kind, message), node)
+    }
+
+    /// Returns `error`, coming from `node` unless it is already located.
+    fn locate(&self, mut error: RuntimeError, node: &dyn Graph<'a>)
+        -> RuntimeError
+    {
+        if error.span.is_none()
+        {
+            error.span = Some(node.span());
+        }
+        error
+    }
+
+    /// Returns the result of the operation `op` in `node`, or an error if it
+    /// overflowed.
+    fn checked(&self, result: Option<Int>, op: &str, node: &dyn Graph<'a>)
+        -> Result<Int, RuntimeError>
+    {
+        result.ok_or_else(|| {
+            self.error(RuntimeErrorKind::Overflow,
+                       format!("attempt to {} with overflow", op), node)
+        })
+    }
+
+    fn io_error(&self, error: std::io::Error, node: &dyn Graph<'a>)
+        -> RuntimeError
+    {
+        self.error(RuntimeErrorKind::Io, error.to_string(), node)
+    }
+
+    /// Returns `N(line)`, ignoring the sign of `line`.
+    fn count(&self, line: &Int) -> Int
+    {
+        line.abs_usize().map(|line| self.interp.count(line))
+            .unwrap_or_default()
+    }
+
+    fn exec(&mut self, statement: &ast::Statement<'a>)
+        -> Result<Outcome, RuntimeError>
+    {
+        match &statement.alt
+        {
+            ast::StatementKind::LineOperations(lineoperations) =>
+            {
+                self.exec_lineops(&lineoperations.lineops)?;
+                Ok(Outcome::Executed)
+            }
+            ast::StatementKind::Again(again) =>
+            {
+                let again_ = self.eval_boolean(&again.boolean)?;
+                match self.exec(&again.statement)?
+                {
+                    Outcome::Executed if again_ => Ok(Outcome::Again),
+                    outcome => Ok(outcome)
+                }
+            }
+            ast::StatementKind::Defer(defer) =>
+            {
+                if self.eval_boolean(&defer.boolean)?
+                {
+                    return Ok(Outcome::Deferred);
+                }
+                self.exec(&defer.statement)
+            }
+            ast::StatementKind::Forget(forget) =>
+            {
+                if self.eval_boolean(&forget.boolean)?
+                {
+                    return Ok(Outcome::Forgotten);
+                }
+                self.exec(&forget.statement)
+            }
+            ast::StatementKind::Print(print) =>
+            {
+                let string = self.eval_string(&print.string)?;
+                self.io.print(&string)
+                    .map_err(|error| self.io_error(error, &**print))?;
+                Ok(Outcome::Executed)
+            }
+        }
+    }
+
+    fn exec_lineops(&mut self, mut lineops: &ast::LineOps<'a>)
+        -> Result<(), RuntimeError>
+    {
+        // Lists are nested as deep as they are long, so they are run in a loop
+        // rather than recursively
+        loop
+        {
+            match &lineops.alt
+            {
+                ast::LineOpsKind::LineOp(lineop) =>
+                    return self.exec_lineop(lineop),
+                ast::LineOpsKind::List(list) =>
+                {
+                    self.exec_lineop(&list.lineop)?;
+                    lineops = &list.list;
+                }
+            }
+        }
+    }
+
+    fn exec_lineop(&mut self, lineop: &ast::LineOp<'a>)
+        -> Result<(), RuntimeError>
+    {
+        let (line, count) = match &lineop.slo.alt
+        {
+            ast::SingleLineOpKind::NumToLineOp(numtolineop) =>
+                (self.eval_int(&numtolineop.num)?, Int::from(1)),
+            ast::SingleLineOpKind::Count(countlineop) =>
+                (self.eval_int(&countlineop.line)?,
+                 self.eval_int(&countlineop.count)?)
+        };
+        let num = match line.abs_usize()
+        {
+            Some(num) if self.interp.lines.contains_key(&num) => num,
+            _ => return Err(self.error(RuntimeErrorKind::NoSuchLine,
+                                       format!("there is no line {}", line),
+                                       lineop))
+        };
+        let delta = if line.is_negative()
+        {
+            let delta = self.interp.mode.neg(&count);
+            self.checked(delta, "negate", lineop)?
+        }
+        else
+        {
+            count
+        };
+        if self.interp.add(num, &delta).is_none()
+        {
+            return Err(self.error(RuntimeErrorKind::Overflow,
+                                  String::from("attempt to add with overflow"),
+                                  lineop));
+        }
+        Ok(())
+    }
+
+    fn eval_absnumber(&mut self, absnumber: &ast::AbsNumber<'a>)
+        -> Result<Int, RuntimeError>
+    {
+        match &absnumber.alt
+        {
+            ast::AbsNumberKind::Number(number) => Ok(number.val.clone()),
+            ast::AbsNumberKind::N(n) =>
+            {
+                let line = self.eval_int(&n.num)?;
+                Ok(self.count(&line))
+            }
+            ast::AbsNumberKind::Read(read) =>
+                self.read().map_err(|error| self.io_error(error, &**read))
+        }
+    }
+
+    fn read(&mut self) -> std::io::Result<Int>
+    {
+        let line = match self.io.read_line()?
+        {
+            Some(line) => line,
+            None => return Ok(Int::from(-1))
+        };
+        let line = line.trim();
+        Ok(match Int::parse(line)
+        {
+            Some(num) => num,
+            None => Int::from(line.chars().next().map_or(-1, |c| c as i64))
+        })
+    }
+
+    /// Evaluates `number`, converting it to a number if it is a string.
+    fn eval_int(&mut self, number: &ast::Number<'a>)
+        -> Result<Int, RuntimeError>
+    {
+        let value = self.eval_number(number)?;
+        value.to_number().map_err(|error| self.locate(error, &number.alt))
+    }
+
+    fn eval_number(&mut self, number: &ast::Number<'a>)
+        -> Result<Value, RuntimeError>
+    {
+        let mode = self.interp.mode;
+        let node = &number.alt;
+        match node
+        {
+            ast::NumberKind::Absolute(absolute) =>
+                Ok(Value::Number(self.eval_absnumber(&absolute.num)?)),
+            ast::NumberKind::UnOp(unop) =>
+            {
+                let num = self.eval_int(&unop.num)?;
+                match unop.op.alt
+                {
+                    ast::UnMathOpKind::Plus(_) => Ok(Value::Number(num)),
+                    ast::UnMathOpKind::Minus(_) =>
+                        Ok(Value::Number(self.checked(mode.neg(&num),
+                                                      "negate", node)?))
+                }
+            }
+            ast::NumberKind::BinOp(binop) =>
+            {
+                // Chains are nested to the left as deep as they are long, so
+                // they are evaluated from their first number rather than
+                // recursively
+                let mut links = vec![&**binop];
+                while let ast::NumberKind::BinOp(binop) =
+                    &links[links.len() - 1].num1.alt
+                {
+                    links.push(binop);
+                }
+                let first = &links[links.len() - 1].num1;
+                let mut value = self.eval_number(first)?;
+                for binop in links.into_iter().rev()
+                {
+                    value = self.eval_binop(value, binop)?;
+                }
+                Ok(value)
+            }
+            ast::NumberKind::Parens(parens) => self.eval_number(&parens.num),
+            ast::NumberKind::StringToNum(stringtonum) =>
+                Ok(Value::String(self.eval_string(&stringtonum.string)?))
+        }
+    }
+
+    /// Applies `binop` to `value1`, the value of its first number.
+    fn eval_binop(&mut self, value1: Value, binop: &ast::BinOpNumber<'a>)
+        -> Result<Value, RuntimeError>
+    {
+        let mode = self.interp.mode;
+        if let ast::BinMathOpKind::Plus(_) = binop.op.alt
+        {
+            let value2 = self.eval_number(&binop.num2)?;
+            if let (Value::Number(num1), Value::Number(num2)) =
+                (&value1, &value2)
+            {
+                let num = mode.add(num1, num2);
+                let num = self.checked(num, "add", binop)?;
+                return Ok(Value::Number(num));
+            }
+            return Ok(Value::String(format!("{}{}", value1, value2)));
+        }
+        let num1 = value1.to_number()
+            .map_err(|error| self.locate(error, &binop.num1.alt))?;
+        let num2 = self.eval_int(&binop.num2)?;
+        let num = match &binop.op.alt
+        {
+            ast::BinMathOpKind::Minus(_) =>
+                self.checked(mode.sub(&num1, &num2), "subtract", binop)?,
+            ast::BinMathOpKind::MathOp(op) if op.val == ast::MulOp::Mul =>
+                self.checked(mode.mul(&num1, &num2), "multiply", binop)?,
+            ast::BinMathOpKind::MathOp(_) =>
+            {
+                if num2.is_zero()
+                {
+                    return Err(self.error(
+                        RuntimeErrorKind::DivisionByZero,
+                        String::from("attempt to divide by zero"),
+                        binop));
+                }
+                self.checked(mode.div(&num1, &num2), "divide", binop)?
+            }
+            ast::BinMathOpKind::Plus(_) => unreachable!()
+        };
+        Ok(Value::Number(num))
+    }
+
+    fn eval_boolean(&mut self, boolean: &ast::Boolean<'a>)
+        -> Result<bool, RuntimeError>
+    {
+        match &boolean.alt
+        {
+            ast::BooleanKind::UnOp(unop) =>
+                Ok(!self.eval_boolean(&unop.boolean)?),
+            ast::BooleanKind::BinOp(binop) =>
+            {
+                // Chains are nested to the left, like those of numbers
+                let mut links = vec![&**binop];
+                while let ast::BooleanKind::BinOp(binop) =
+                    &links[links.len() - 1].boolean1.alt
+                {
+                    links.push(binop);
+                }
+                let first = &links[links.len() - 1].boolean1;
+                let mut value = self.eval_boolean(first)?;
+                for binop in links.into_iter().rev()
+                {
+                    value = match binop.op.val
+                    {
+                        ast::BoolOp::And =>
+                            value && self.eval_boolean(&binop.boolean2)?,
+                        ast::BoolOp::Or =>
+                            value || self.eval_boolean(&binop.boolean2)?
+                    };
+                }
+                Ok(value)
+            }
+            ast::BooleanKind::BinOpNum(binop) =>
+            {
+                let num1 = self.eval_int(&binop.num1)?;
+                let num2 = self.eval_int(&binop.num2)?;
+                Ok(match binop.op.val
+                {
+                    ast::CmpOp::Eq => num1 == num2,
+                    ast::CmpOp::Ne => num1 != num2,
+                    ast::CmpOp::Lt => num1 < num2,
+                    ast::CmpOp::Le => num1 <= num2,
+                    ast::CmpOp::Gt => num1 > num2,
+                    ast::CmpOp::Ge => num1 >= num2
+                })
+            }
+            ast::BooleanKind::Parens(parens) =>
+                self.eval_boolean(&parens.boolean),
+            ast::BooleanKind::NumToBool(numtobool) =>
+            {
+                let line = self.eval_int(&numtobool.num)?;
+                Ok(!self.count(&line).is_zero())
+            }
+        }
+    }
+
+    fn eval_string(&mut self, string: &ast::String_<'a>)
+        -> Result<String, RuntimeError>
+    {
+        match &string.alt
+        {
+            ast::StringKind::String(token) => Ok(token.val.clone()),
+            ast::StringKind::U(u) =>
+            {
+                let num = self.eval_absnumber(&u.num)?;
+                num.to_u32().and_then(char::from_u32).map(String::from)
+                    .ok_or_else(|| {
+                        self.error(RuntimeErrorKind::InvalidCharacter,
+                                   format!("{} is not a Unicode character",
+                                           num),
+                                   &**u)
+                    })
+            }
+            ast::StringKind::Concat(concat) =>
+            {
+                // Concatenations are nested to the left, like chains of
+                // numbers
+                let mut links = vec![&**concat];
+                while let ast::StringKind::Concat(concat) =
+                    &links[links.len() - 1].str1.alt
+                {
+                    links.push(concat);
+                }
+                let mut string =
+                    self.eval_string(&links[links.len() - 1].str1)?;
+                for concat in links.into_iter().rev()
+                {
+                    string += &self.eval_string(&concat.str2)?;
+                }
+                Ok(string)
+            }
+            ast::StringKind::NumToString(numtostring) =>
+                Ok(self.eval_number(&numtostring.num)?.to_string())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(program: &str, input: &str) -> Result<String, RuntimeError>
+    {
+        let program = crate::parse_program(program).unwrap();
+        let mut io = crate::io::Memory::new(input);
+        Interpreter::with_seed(&program, 0)?.run(&mut io)?;
+        Ok(io.output)
+    }
+
+    #[test]
+    fn statement_check()
+    {
+        assert_eq!(run("1 forget (1) print(\"a\");", ""),
+                   Ok(String::new()));
+        assert_eq!(run("1 again (N(2)<3) 2;\n2 defer (1) print(N(2));", ""),
+                   Ok(String::from("4\n3\n2\n1\n")));
+        assert_eq!(run("1 print(U(read())+U(read())+(read()*2));",
+                       "72\ni\n3\n"),
+                   Ok(String::from("Hi6\n")));
+        assert_eq!(run("1 print(\"a\\\"b\"+1+2);", ""),
+                   Ok(String::from("a\"b12\n")));
+        assert_eq!(run("1 print(\"n=\"+N(1)-1+\"!\"+3-4*2);", ""),
+                   Ok(String::from("n=0!-5\n")));
+    }
+
+    #[test]
+    fn error_check()
+    {
+        let error = run("1 2;", "").err().unwrap();
+        assert_eq!(error.kind, RuntimeErrorKind::NoSuchLine);
+        assert_eq!(error.to_string(), "line 1: there is no line 2");
+
+        let error = run("1 print(1/(N(1)-1));", "").err().unwrap();
+        assert_eq!(error.kind, RuntimeErrorKind::DivisionByZero);
+
+        let error = run("1 print(2*\"a\");", "").err().unwrap();
+        assert_eq!(error.kind, RuntimeErrorKind::NotANumber);
+
+        let error = run("1 1;\n1 2;", "").err().unwrap();
+        assert_eq!(error.kind, RuntimeErrorKind::DuplicateLine);
+        assert_eq!(error.span, Some(Span::new(5, 6)));
+    }
+
+    #[test]
+    #[cfg(not(feature = "bigint"))]
+    fn overflow_check()
+    {
+        let input = "1 print(-9223372036854775807-2);";
+        let program = crate::parse_program(input).unwrap();
+        let mut interpreter = Interpreter::with_seed(&program, 0).unwrap();
+        let error = interpreter.run(&mut crate::io::Memory::new(""))
+            .err().unwrap();
+        assert_eq!(error.kind, RuntimeErrorKind::Overflow);
+        assert_eq!(error.to_string(),
+                   "line 1: attempt to subtract with overflow");
+        assert_eq!(error.span, Some(Span::new(8, 30)));
+
+        let modes = [(OverflowMode::Wrapping, "9223372036854775807\n"),
+                     (OverflowMode::Saturating, "-9223372036854775808\n")];
+        for (mode, expected) in &modes
+        {
+            let mut interpreter = Interpreter::with_seed(&program, 0).unwrap();
+            interpreter.set_overflow_mode(*mode);
+            let mut io = crate::io::Memory::new("");
+            interpreter.run(&mut io).unwrap();
+            assert_eq!(io.output, *expected);
+        }
+    }
+}
@@ -0,0 +1,204 @@
+use std::io::{self, BufRead, Write};
+
+/// Where `read()` takes its input from, and `print` writes its output to.
+pub trait Io
+{
+    /// Returns the next line of input, without its line terminator, or `None`
+    /// at the end of input.
+    fn read_line(&mut self) -> io::Result<Option<String>>;
+
+    /// Outputs `text`, the string given to a `print` statement, followed by a
+    /// newline.
+    fn print(&mut self, text: &str) -> io::Result<()>;
+}
+
+fn trim_newline(mut line: String) -> String
+{
+    if line.ends_with('\n')
+    {
+        line.pop();
+        if line.ends_with('\r')
+        {
+            line.pop();
+        }
+    }
+    line
+}
+
+/// Reads from the standard input and writes to the standard output.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct StdIo;
+
+impl Io for StdIo
+{
+    fn read_line(&mut self) -> io::Result<Option<String>>
+    {
+        let mut line = String::new();
+        match io::stdin().lock().read_line(&mut line)?
+        {
+            0 => Ok(None),
+            _ => Ok(Some(trim_newline(line)))
+        }
+    }
+
+    fn print(&mut self, text: &str) -> io::Result<()>
+    {
+        let mut stdout = io::stdout();
+        writeln!(stdout, "{}", text)?;
+        stdout.flush()
+    }
+}
+
+/// Reads from any `BufRead` and writes to any `Write`, such as a `&[u8]` and
+/// a `Vec<u8>`.
+#[derive(Debug)]
+pub struct Streams<R, W>
+{
+    pub input: R,
+    pub output: W
+}
+
+impl<R: BufRead, W: Write> Streams<R, W>
+{
+    pub fn new(input: R, output: W) -> Streams<R, W>
+    {
+        Streams { input, output }
+    }
+}
+
+impl<R: BufRead, W: Write> Io for Streams<R, W>
+{
+    fn read_line(&mut self) -> io::Result<Option<String>>
+    {
+        let mut line = String::new();
+        match self.input.read_line(&mut line)?
+        {
+            0 => Ok(None),
+            _ => Ok(Some(trim_newline(line)))
+        }
+    }
+
+    fn print(&mut self, text: &str) -> io::Result<()>
+    {
+        writeln!(self.output, "{}", text)
+    }
+}
+
+/// Reads from a string and writes to a string.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Memory
+{
+    input: String,
+    pos: usize,
+    /// Everything printed so far.
+    pub output: String
+}
+
+impl Memory
+{
+    /// Returns an `Io` whose input is `input`, and with an empty output.
+    pub fn new(input: &str) -> Memory
+    {
+        Memory { input: String::from(input), pos: 0, output: String::new() }
+    }
+}
+
+impl Io for Memory
+{
+    fn read_line(&mut self) -> io::Result<Option<String>>
+    {
+        let rest = &self.input[self.pos..];
+        if rest.is_empty()
+        {
+            return Ok(None);
+        }
+        let len = rest.find('\n').map_or(rest.len(), |pos| pos + 1);
+        let line = String::from(&rest[..len]);
+        self.pos += len;
+        Ok(Some(trim_newline(line)))
+    }
+
+    fn print(&mut self, text: &str) -> io::Result<()>
+    {
+        self.output += text;
+        self.output.push('\n');
+        Ok(())
+    }
+}
+
+/// Calls `read` for each line of input, and `print` for each printed string.
+pub struct Callbacks<R, P>
+{
+    read: R,
+    print: P
+}
+
+impl<R, P> Callbacks<R, P>
+    where R: FnMut() -> Option<String>,
+          P: FnMut(&str)
+{
+    pub fn new(read: R, print: P) -> Callbacks<R, P>
+    {
+        Callbacks { read, print }
+    }
+}
+
+impl<R, P> Io for Callbacks<R, P>
+    where R: FnMut() -> Option<String>,
+          P: FnMut(&str)
+{
+    fn read_line(&mut self) -> io::Result<Option<String>>
+    {
+        Ok((self.read)())
+    }
+
+    fn print(&mut self, text: &str) -> io::Result<()>
+    {
+        (self.print)(text);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn memory_check()
+    {
+        let mut io = Memory::new("1\r\n\nlast");
+        assert_eq!(io.read_line().unwrap(), Some(String::from("1")));
+        assert_eq!(io.read_line().unwrap(), Some(String::new()));
+        assert_eq!(io.read_line().unwrap(), Some(String::from("last")));
+        assert_eq!(io.read_line().unwrap(), None);
+        io.print("a").unwrap();
+        io.print("b").unwrap();
+        assert_eq!(io.output, "a\nb\n");
+    }
+
+    #[test]
+    fn streams_check()
+    {
+        let mut io = Streams::new("x\ny\n".as_bytes(), Vec::new());
+        assert_eq!(io.read_line().unwrap(), Some(String::from("x")));
+        assert_eq!(io.read_line().unwrap(), Some(String::from("y")));
+        assert_eq!(io.read_line().unwrap(), None);
+        io.print("z").unwrap();
+        assert_eq!(io.output, b"z\n");
+    }
+
+    #[test]
+    fn callbacks_check()
+    {
+        let mut printed = Vec::new();
+        {
+            let read = || Some(String::from("42"));
+            let mut io = Callbacks::new(read, |text: &str| {
+                printed.push(String::from(text))
+            });
+            assert_eq!(io.read_line().unwrap(), Some(String::from("42")));
+            io.print("a").unwrap();
+        }
+        assert_eq!(printed, ["a"]);
+    }
+}
@@ -1,3 +1,11 @@
+use std::iter::FusedIterator;
+
+use crate::ast::CmpOp;
+use crate::error::{ErrorKind, ParseError};
+use crate::num::Int;
+use crate::span::Span;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum TokenVariant
 {
     // Whenever specific tokens
@@ -15,12 +23,19 @@ pub enum TokenVariant
     Minus, // -
 
     // Usual tokens
-    Number(usize),
-    String,
-    UnBoolOp, // !
-    BinBoolOp, // && ||
-    BinNumBoolOp, // == != < <= > >=
-    MathOp, // * /
+    Number(Int),
+    String(String), // Decoded value
+    Not, // !
+    And, // &&
+    Or, // ||
+    Equal, // ==
+    NotEqual, // !=
+    Less, // <
+    LessEqual, // <=
+    Greater, // >
+    GreaterEqual, // >=
+    Star, // *
+    Slash, // /
 
     // Separator tokens
     Comma,
@@ -29,57 +44,293 @@ pub enum TokenVariant
     Semicolon,
     Sharp,
 
+    // Trivia, only returned by `eat_trivia` and `Lexer::with_trivia`
+    Whitespace,
+    Comment, // // and /* */
+
     // End of input
     EOI
 }
 
+impl TokenVariant
+{
+    /// Returns whether the token compares two numbers.
+    pub fn is_comparison(&self) -> bool
+    {
+        self.cmp_op().is_some()
+    }
+
+    /// Returns the comparison done by the token, if it compares two numbers.
+    pub fn cmp_op(&self) -> Option<CmpOp>
+    {
+        match self
+        {
+            TokenVariant::Less => Some(CmpOp::Lt),
+            TokenVariant::LessEqual => Some(CmpOp::Le),
+            TokenVariant::Greater => Some(CmpOp::Gt),
+            TokenVariant::GreaterEqual => Some(CmpOp::Ge),
+            TokenVariant::Equal => Some(CmpOp::Eq),
+            TokenVariant::NotEqual => Some(CmpOp::Ne),
+            _ => None
+        }
+    }
+
+    /// Returns whether the token is whitespace or a comment.
+    pub fn is_trivia(&self) -> bool
+    {
+        matches!(self, TokenVariant::Whitespace | TokenVariant::Comment)
+    }
+}
+
+#[derive(Clone, Debug)]
 pub struct Token<'a>
 {
     pub tok: &'a str,
-    pub variant: TokenVariant
+    pub variant: TokenVariant,
+    pub span: Span
+}
+
+const KEYWORDS: [&str; 7] = ["again", "defer", "forget", "N", "print", "read",
+                             "U"];
+
+/// Returns the keyword `word` was probably meant to be, if it only differs
+/// from one by case.
+pub fn suggest_keyword(word: &str) -> Option<&'static str>
+{
+    KEYWORDS.iter().copied().find(|keyword| keyword.eq_ignore_ascii_case(word))
+}
+
+/// Maximum nesting depth of the nodes parsed from a cursor, unless set with
+/// `Cursor::with_max_depth`.
+///
+/// Parsing that deep fits in the 2 MiB stack of a new thread, even in debug
+/// builds.
+pub const DEFAULT_MAX_DEPTH: usize = 64;
+
+/// A position in the input.
+///
+/// Unlike a bare slice of the remaining input, a cursor remembers the whole
+/// input, so that positions can be reported relative to its beginning. It also
+/// keeps track of how deeply nested the node being parsed is, so that the
+/// parser does not overflow the stack.
+#[derive(Clone, Copy)]
+pub struct Cursor<'a>
+{
+    src: &'a str,
+    pos: usize,
+    depth: usize,
+    max_depth: usize
+}
+
+impl<'a> Cursor<'a>
+{
+    /// Returns a cursor at the beginning of `src`.
+    pub fn new(src: &'a str) -> Cursor<'a>
+    {
+        Cursor { src, pos: 0, depth: 0, max_depth: DEFAULT_MAX_DEPTH }
+    }
+
+    /// Returns the same cursor, allowing nodes to be nested up to
+    /// `max_depth` levels deep.
+    pub fn with_max_depth(self, max_depth: usize) -> Cursor<'a>
+    {
+        Cursor { max_depth, ..self }
+    }
+
+    /// Returns the nesting depth of the node being parsed.
+    pub fn depth(&self) -> usize
+    {
+        self.depth
+    }
+
+    /// Returns the maximum nesting depth of the nodes parsed from the cursor.
+    pub fn max_depth(&self) -> usize
+    {
+        self.max_depth
+    }
+
+    /// Returns the same cursor, at nesting depth `depth`.
+    pub fn with_depth(self, depth: usize) -> Cursor<'a>
+    {
+        Cursor { depth, ..self }
+    }
+
+    /// Returns the whole input.
+    pub fn src(&self) -> &'a str
+    {
+        self.src
+    }
+
+    /// Returns the offset of the cursor in the input, in bytes.
+    pub fn pos(&self) -> usize
+    {
+        self.pos
+    }
+
+    /// Returns the input from the cursor to the end.
+    pub fn rest(&self) -> &'a str
+    {
+        &self.src[self.pos..]
+    }
+
+    /// Returns a cursor `len` bytes further in the input.
+    pub fn advance(self, len: usize) -> Cursor<'a>
+    {
+        Cursor { pos: self.pos + len, ..self }
+    }
+
+    /// Returns the span of the `len` bytes from the cursor.
+    pub fn span(&self, len: usize) -> Span
+    {
+        Span::new(self.pos, self.pos + len)
+    }
+}
+
+/// Decodes the escape sequence `s` starts with, such as `\\n`.
+///
+/// Returns the character and the length of the sequence, or the length of the
+/// invalid sequence.
+fn decode_escape(s: &str) -> Result<(char, usize), usize>
+{
+    match s[1..].chars().next()
+    {
+        None => Err(1),
+        Some('"') => Ok(('"', 2)),
+        Some('\\') => Ok(('\\', 2)),
+        Some('n') => Ok(('\n', 2)),
+        Some('t') => Ok(('\t', 2)),
+        Some('u') =>
+        {
+            // \u{X} to \u{XXXXXX}
+            if !s[2..].starts_with('{')
+            {
+                return Err(2);
+            }
+            let end = s[3..].find(|c: char| !c.is_ascii_hexdigit())
+                .map_or(s.len(), |len| 3 + len);
+            if !s[end..].starts_with('}')
+            {
+                return Err(end);
+            }
+            let digits = &s[3..end];
+            let c = if (1..=6).contains(&digits.len())
+            {
+                u32::from_str_radix(digits, 16).ok().and_then(char::from_u32)
+            }
+            else
+            {
+                None
+            };
+            c.map(|c| (c, end + 1)).ok_or(end + 1)
+        }
+        Some(e) => Err(1 + e.len_utf8())
+    }
+}
+
+/// Reads whitespace or a comment from the input, if there is one there.
+fn eat_trivium<'a>(input: Cursor<'a>)
+    -> Result<Option<(Token<'a>, Cursor<'a>)>, ParseError>
+{
+    let rest = input.rest();
+    let (variant, len) = if rest.starts_with("//")
+    {
+        let line = rest.split('\n').next().unwrap();
+        (TokenVariant::Comment, line.trim_end_matches('\r').len())
+    }
+    else if let Some(comment) = rest.strip_prefix("/*")
+    {
+        match comment.find("*/")
+        {
+            Some(pos) => (TokenVariant::Comment, pos + 4),
+            None => return Err(ParseError::new(
+                ErrorKind::UnterminatedComment, input.src(),
+                input.span(rest.len())))
+        }
+    }
+    else
+    {
+        match rest.len() - rest.trim_start().len()
+        {
+            0 => return Ok(None),
+            len => (TokenVariant::Whitespace, len)
+        }
+    };
+    let token = Token { tok: &rest[..len], variant, span: input.span(len) };
+    Ok(Some((token, input.advance(len))))
+}
+
+/// Skips whitespace and comments.
+///
+/// Comments run from `//` to the end of the line, or from `/*` to the next
+/// `*/`.
+///
+/// Returns the trivia skipped, as `TokenVariant::Whitespace` and
+/// `TokenVariant::Comment` tokens, and a cursor to the next token.
+///
+/// # Errors
+///
+/// Will return `Err` if end of input is reached in a `/*` comment.
+pub fn eat_trivia<'a>(input: Cursor<'a>)
+    -> Result<(Vec<Token<'a>>, Cursor<'a>), ParseError>
+{
+    let mut trivia = Vec::new();
+    let mut cursor = input;
+    while let Some((token, next)) = eat_trivium(cursor)?
+    {
+        trivia.push(token);
+        cursor = next;
+    }
+    Ok((trivia, cursor))
 }
 
 /// Reads a token from the input.
 ///
-/// Returns a token and a slice from the end of the token to the end of the
-/// input.
+/// Returns a token and a cursor to the end of the token.
 ///
-/// Note: difference between input and output slices may be more than the length
-/// of the token, as leading whitespace is ignored.
+/// Note: difference between input and output cursors may be more than the
+/// length of the token, as leading whitespace and comments are ignored (see
+/// `eat_trivia`).
 ///
 /// # Errors
 ///
 /// Will return `Err` if:
-/// * end of input is reached in an unfinished token (such as a string)
-/// * a number contains an invalid digit
+/// * end of input is reached in an unfinished token (such as a string) or
+///   comment
+/// * a string contains an unknown or invalid escape sequence
+/// * a number contains an invalid digit, or is too large
 /// * token is not known (reminder: keywords are case sensitive)
-///
-/// `Err`'s contents are a description of the issue and the slice where it
-/// happened.
-pub fn eat<'a>(input: &'a str)
-    -> Result<(Token<'a>, &'a str), (String, &'a str)>
+pub fn eat<'a>(input: Cursor<'a>)
+    -> Result<(Token<'a>, Cursor<'a>), ParseError>
 {
-    let input = input.trim_start();
+    let mut input = input;
+    while let Some((_, next)) = eat_trivium(input)?
+    {
+        input = next;
+    }
+    let rest = input.rest();
 
 macro_rules! make_token
 {
     ($type: expr, $input: ident, $pos: expr) =>
     {
         {
-            let (token, output) = $input.split_at($pos);
-            Ok((Token { tok: token, variant: $type }, output))
+            let token = Token { tok: &$input.rest()[..$pos],
+                                variant: $type,
+                                span: $input.span($pos) };
+            Ok((token, $input.advance($pos)))
         }
     };
 }
 
-    match input.chars().next()
+    match rest.chars().next()
     {
         None => make_token!(TokenVariant::EOI, input, 0),
         Some('+') => make_token!(TokenVariant::Plus, input, 1),
         Some('-') => make_token!(TokenVariant::Minus, input, 1),
-        Some('*') | Some('/') =>
-            make_token!(TokenVariant::MathOp, input, 1),
-        Some('!') => make_token!(TokenVariant::UnBoolOp, input, 1),
+        Some('*') => make_token!(TokenVariant::Star, input, 1),
+        Some('/') => make_token!(TokenVariant::Slash, input, 1),
+        Some('!') if rest.get(..2) != Some("!=") =>
+            make_token!(TokenVariant::Not, input, 1),
         Some(',') => make_token!(TokenVariant::Comma, input, 1),
         Some('(') => make_token!(TokenVariant::LeftParens, input, 1),
         Some(')') => make_token!(TokenVariant::RightParens, input, 1),
@@ -87,21 +338,42 @@ macro_rules! make_token
         Some('#') => make_token!(TokenVariant::Sharp, input, 1),
         Some('"') =>
         {
-            let mut escape = false;
+            let mut val = String::new();
+            let mut pos = 1;
 
-            for (i, c) in input.char_indices().skip(1)
+            while let Some(c) = rest[pos..].chars().next()
             {
                 match c
                 {
-                    '"' if escape == false => {
-                        return make_token!(TokenVariant::String, input, i + 1)
+                    '"' => return make_token!(TokenVariant::String(val), input,
+                                              pos + 1),
+                    '\\' => match decode_escape(&rest[pos..])
+                    {
+                        Ok((c, len)) =>
+                        {
+                            val.push(c);
+                            pos += len;
+                        }
+                        // The sequence is only unfinished
+                        Err(len) if pos + len == rest.len() => break,
+                        Err(len) =>
+                        {
+                            let span = Span::new(input.pos() + pos,
+                                                 input.pos() + pos + len);
+                            return Err(ParseError::new(
+                                ErrorKind::InvalidEscape, input.src(), span));
+                        }
                     },
-                    '\\' if escape == false => escape = true,
-                    _ => escape = false,
+                    _ =>
+                    {
+                        val.push(c);
+                        pos += c.len_utf8();
+                    }
                 }
             }
 
-            Err((String::from("End of input while reading string"), input))
+            Err(ParseError::new(ErrorKind::UnterminatedString, input.src(),
+                                input.span(rest.len())))
         }
         Some(c) if c.is_ascii_digit() => // Number
         {
@@ -110,12 +382,12 @@ macro_rules! make_token
             let mut end = 0;
             if c == '0'
             {
-                match input.chars().skip(1).next()
+                match rest.chars().nth(1)
                 {
                     Some('b') => // 0bXXXX...: binary
                     {
                         let mut pos = 2;
-                        for (i, c) in input.char_indices().skip(2)
+                        for (i, c) in rest.char_indices().skip(2)
                         {
                             if c != '0' && c != '1'
                             {
@@ -133,7 +405,7 @@ macro_rules! make_token
                     Some('x') | Some('X') => // 0xXXXX...: hexadecimal
                     {
                         let mut pos = 2;
-                        for (i, c) in input.char_indices().skip(2)
+                        for (i, c) in rest.char_indices().skip(2)
                         {
                             if !c.is_ascii_hexdigit()
                             {
@@ -159,7 +431,7 @@ macro_rules! make_token
             }
             if end == 0
             {
-                for (i, c) in input.char_indices().skip(start)
+                for (i, c) in rest.char_indices().skip(start)
                 {
                     if !c.is_ascii_digit()
                     {
@@ -169,19 +441,25 @@ macro_rules! make_token
                 }
             }
 
-            let (tok, output) = input.split_at(end);
-            match usize::from_str_radix(&tok[start..], base)
+            let digits = &rest[start..end];
+            if let Some(i) = digits.find(|c: char| !c.is_digit(base))
+            {
+                let span = Span::new(input.pos() + start + i,
+                                     input.pos() + start + i + 1);
+                return Err(ParseError::new(ErrorKind::InvalidDigit,
+                                           input.src(), span));
+            }
+            match Int::from_str_radix(digits, base)
             {
-                Ok(num) => Ok((Token { tok,
-                                       variant: TokenVariant::Number(num) },
-                               output)),
-                Err(error) => Err((error.to_string(), &tok[start..]))
+                Some(num) => make_token!(TokenVariant::Number(num), input, end),
+                None => Err(ParseError::new(ErrorKind::Overflow, input.src(),
+                                            input.span(end)))
             }
         }
         Some(c) if c.is_ascii_alphabetic() =>
         {
             let mut end = 1;
-            for (i, c) in input.char_indices().skip(1)
+            for (i, c) in rest.char_indices().skip(1)
             {
                 if !c.is_ascii_alphabetic()
                 {
@@ -190,7 +468,7 @@ macro_rules! make_token
                 end = i + 1
             }
 
-            match &input[..end]
+            match &rest[..end]
             {
                 "again" => make_token!(TokenVariant::Again, input, end),
                 "defer" => make_token!(TokenVariant::Defer, input, end),
@@ -199,32 +477,118 @@ macro_rules! make_token
                 "print" => make_token!(TokenVariant::Print, input, end),
                 "read" => make_token!(TokenVariant::Read, input, end),
                 "U" => make_token!(TokenVariant::U, input, end),
-                _ => Err((String::from("Unknown token"), input))
+                _ => Err(ParseError::new(ErrorKind::UnknownKeyword,
+                                         input.src(), input.span(end)))
             }
         }
-        Some('<') | Some('>') =>
+        Some('<') if rest.get(..2) == Some("<=") =>
+            make_token!(TokenVariant::LessEqual, input, 2),
+        Some('<') => make_token!(TokenVariant::Less, input, 1),
+        Some('>') if rest.get(..2) == Some(">=") =>
+            make_token!(TokenVariant::GreaterEqual, input, 2),
+        Some('>') => make_token!(TokenVariant::Greater, input, 1),
+        Some(c) =>
         {
-            match input.chars().skip(1).next()
+            // Match the remaining tokens: && || == !=
+            match rest.get(..2)
             {
-                Some('=') => make_token!(TokenVariant::BinNumBoolOp, input, 2),
-                _ => make_token!(TokenVariant::BinNumBoolOp, input, 1)
+                Some("&&") => make_token!(TokenVariant::And, input, 2),
+                Some("||") => make_token!(TokenVariant::Or, input, 2),
+                Some("==") => make_token!(TokenVariant::Equal, input, 2),
+                Some("!=") => make_token!(TokenVariant::NotEqual, input, 2),
+                _ => Err(ParseError::new(ErrorKind::UnknownToken, input.src(),
+                                         input.span(c.len_utf8())))
             }
         }
-        Some(_) =>
+    }
+}
+
+/// An iterator over the tokens of an input.
+///
+/// The iterator ends at end of input, without yielding `TokenVariant::EOI`,
+/// or after the first error.
+///
+/// With trivia, the tokens cover the whole input, so that concatenating them
+/// gives it back.
+pub struct Lexer<'a>
+{
+    cursor: Cursor<'a>,
+    trivia: bool,
+    done: bool
+}
+
+impl<'a> Lexer<'a>
+{
+    /// Returns an iterator over the tokens of `src`, skipping whitespace and
+    /// comments.
+    pub fn new(src: &'a str) -> Lexer<'a>
+    {
+        Lexer { cursor: Cursor::new(src), trivia: false, done: false }
+    }
+
+    /// Returns an iterator over the tokens of `src`, including whitespace and
+    /// comments as `TokenVariant::Whitespace` and `TokenVariant::Comment`
+    /// tokens.
+    pub fn with_trivia(src: &'a str) -> Lexer<'a>
+    {
+        Lexer { cursor: Cursor::new(src), trivia: true, done: false }
+    }
+
+    /// Returns a cursor to the input not read yet.
+    pub fn cursor(&self) -> Cursor<'a>
+    {
+        self.cursor
+    }
+}
+
+impl<'a> Iterator for Lexer<'a>
+{
+    type Item = Result<Token<'a>, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item>
+    {
+        if self.done
         {
-            // Match the remaining tokens: && || == !=
-            match input.get(..2)
+            return None;
+        }
+
+        let res = if self.trivia
+        {
+            match eat_trivium(self.cursor)
+            {
+                Ok(Some(trivium)) => Ok(trivium),
+                Ok(None) => eat(self.cursor),
+                Err(error) => Err(error)
+            }
+        }
+        else
+        {
+            eat(self.cursor)
+        };
+
+        match res
+        {
+            Ok((token, _)) if token.variant == TokenVariant::EOI =>
+            {
+                self.done = true;
+                None
+            }
+            Ok((token, cursor)) =>
             {
-                Some("&&") | Some("||") =>
-                    make_token!(TokenVariant::BinBoolOp, input, 2),
-                Some("==") | Some("!=") =>
-                    make_token!(TokenVariant::BinNumBoolOp, input, 2),
-                _ => Err((String::from("Unknown token"), input))
+                self.cursor = cursor;
+                Some(Ok(token))
+            }
+            Err(error) =>
+            {
+                self.done = true;
+                Some(Err(error))
             }
         }
     }
 }
 
+impl FusedIterator for Lexer<'_> {}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -236,13 +600,14 @@ mod tests {
             {
                 let input = $input;
 
-                let res = eat(input);
+                let res = eat(Cursor::new(input));
                 if let Ok((token, output)) = res
                 {
                     if let $type = token.variant
                     {
                         assert_eq!(token.tok, &input[$from..$at]);
-                        assert_eq!(output, &input[$at..]);
+                        assert_eq!(token.span, Span::new($from, $at));
+                        assert_eq!(output.rest(), &input[$at..]);
                     }
                     else
                     {
@@ -264,6 +629,27 @@ mod tests {
         check_token!("    \t\n ", TokenVariant::EOI, 7, 7)
     }
 
+    #[test]
+    fn comment_check()
+    {
+        check_token!("// a comment\r\n/* b\n c */ /**/+", TokenVariant::Plus,
+                     29, 30);
+        check_token!("1// no space", TokenVariant::Number(_), 0, 1);
+        check_token!("/ /", TokenVariant::Slash, 0, 1);
+
+        let input = " // a\n/* b */ 1 // c";
+        let (trivia, cursor) = eat_trivia(Cursor::new(input)).unwrap();
+        let trivia: Vec<_> = trivia.iter().map(|token| {
+            (token.tok, token.span)
+        }).collect();
+        assert_eq!(trivia, vec![(" ", Span::new(0, 1)),
+                                ("// a", Span::new(1, 5)),
+                                ("\n", Span::new(5, 6)),
+                                ("/* b */", Span::new(6, 13)),
+                                (" ", Span::new(13, 14))]);
+        assert_eq!(cursor.rest(), "1 // c");
+    }
+
     #[test]
     fn plus_check()
     {
@@ -274,13 +660,49 @@ mod tests {
     fn string_check()
     {
         // Actual bytes: "ab\"\\"de
-        check_token!("\"ab\\\"\\\\\"de", TokenVariant::String, 0, 8)
+        check_token!("\"ab\\\"\\\\\"de", TokenVariant::String(_), 0, 8);
+
+        let input = "\"a\\\"b\\\\c\\nd\\te\\u{1F4BB}\\u{e9}\"";
+        let (token, _) = eat(Cursor::new(input)).unwrap();
+        if let TokenVariant::String(val) = token.variant
+        {
+            assert_eq!(val, "a\"b\\c\nd\te💻é");
+        }
+        else
+        {
+            panic!("Not a TokenVariant::String");
+        }
+    }
+
+    #[test]
+    fn not_equal_check()
+    {
+        check_token!("!=1", TokenVariant::NotEqual, 0, 2);
+        check_token!("!1", TokenVariant::Not, 0, 1);
+    }
+
+    #[test]
+    fn operator_check()
+    {
+        check_token!("<=1", TokenVariant::LessEqual, 0, 2);
+        check_token!("< =1", TokenVariant::Less, 0, 1);
+        check_token!(">=>", TokenVariant::GreaterEqual, 0, 2);
+        check_token!(">>", TokenVariant::Greater, 0, 1);
+        check_token!("==", TokenVariant::Equal, 0, 2);
+        check_token!("&&&", TokenVariant::And, 0, 2);
+        check_token!("||", TokenVariant::Or, 0, 2);
+        check_token!("**", TokenVariant::Star, 0, 1);
+
+        assert_eq!(TokenVariant::NotEqual.cmp_op(), Some(CmpOp::Ne));
+        assert_eq!(TokenVariant::GreaterEqual.cmp_op(), Some(CmpOp::Ge));
+        assert_eq!(TokenVariant::Not.cmp_op(), None);
+        assert!(!TokenVariant::And.is_comparison());
     }
 
     #[test]
     fn skip_spaces()
     {
-        check_token!("       \t\n !", TokenVariant::UnBoolOp, 10, 11)
+        check_token!("       \t\n !", TokenVariant::Not, 10, 11)
     }
 
     macro_rules! check_token_number
@@ -290,14 +712,14 @@ mod tests {
             {
                 let input = $input;
 
-                let res = eat(input);
+                let res = eat(Cursor::new(input));
                 if let Ok((token, output)) = res
                 {
                     if let TokenVariant::Number(num) = token.variant
                     {
                         assert_eq!(token.tok, &input[$from..$at]);
-                        assert_eq!(num, $val);
-                        assert_eq!(output, &input[$at..]);
+                        assert_eq!(num, Int::from($val));
+                        assert_eq!(output.rest(), &input[$at..]);
                     }
                     else
                     {
@@ -338,14 +760,20 @@ mod tests {
 
     macro_rules! check_token_error
     {
-        ($input: expr) =>
+        ($input: expr, $kind: expr, $from: expr, $at: expr) =>
         {
             {
                 let input = $input;
 
-                let res = eat(input);
-
-                assert!(res.is_err());
+                match eat(Cursor::new(input))
+                {
+                    Err(error) =>
+                    {
+                        assert_eq!(error.kind, $kind);
+                        assert_eq!(error.span, Span::new($from, $at));
+                    }
+                    Ok(_) => panic!("Not an error")
+                }
             }
         }
     }
@@ -353,11 +781,86 @@ mod tests {
     #[test]
     fn error_check()
     {
-        check_token_error!("\"abcd"); // End of input while in string
-        check_token_error!("089"); // Wrong digits for base
-        check_token_error!("Again"); // Wrong case keyword
-        check_token_error!("abcd"); // Unknown token
-        check_token_error!("💻"); // Unknown non-ASCII token
+        // End of input while in string
+        check_token_error!("\"abcd", ErrorKind::UnterminatedString, 0, 5);
+        // End of input while in comment
+        check_token_error!(" /* a */ /* b", ErrorKind::UnterminatedComment,
+                           9, 13);
+        // Wrong digits for base
+        check_token_error!("089", ErrorKind::InvalidDigit, 1, 2);
+        // Wrong case keyword
+        check_token_error!("Again", ErrorKind::UnknownKeyword, 0, 5);
+        // Unknown token
+        check_token_error!(" abcd", ErrorKind::UnknownKeyword, 1, 5);
+        // Unknown non-ASCII token
+        check_token_error!("💻", ErrorKind::UnknownToken, 0, 4);
+        check_token_error!("=", ErrorKind::UnknownToken, 0, 1);
+        check_token_error!("&|", ErrorKind::UnknownToken, 0, 1);
+        // Too large for a number
+        if !cfg!(feature = "bigint")
+        {
+            check_token_error!("0x1ffffffffffffffffffff", ErrorKind::Overflow,
+                               0, 23);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "bigint")]
+    fn big_number_check()
+    {
+        let (token, _) = eat(Cursor::new("0x1ffffffffffffffffffff")).unwrap();
+        if let TokenVariant::Number(num) = token.variant
+        {
+            assert_eq!(num.to_string(), "2417851639229258349412351");
+        }
+        else
+        {
+            panic!("Not a TokenVariant::Number");
+        }
+    }
+
+    #[test]
+    fn escape_error_check()
+    {
+        check_token_error!("\"a\\qb\"", ErrorKind::InvalidEscape, 2, 4);
+        check_token_error!("\"\\é\"", ErrorKind::InvalidEscape, 1, 4);
+        check_token_error!("\"\\u00e9\"", ErrorKind::InvalidEscape, 1, 3);
+        check_token_error!("\"\\u{}\"", ErrorKind::InvalidEscape, 1, 5);
+        check_token_error!("\"\\u{d800}\"", ErrorKind::InvalidEscape, 1, 9);
+        check_token_error!("\"\\u{1234567}\"", ErrorKind::InvalidEscape,
+                           1, 12);
+        check_token_error!("\"\\u{12\"", ErrorKind::InvalidEscape, 1, 6);
+        // End of input in escape sequences
+        check_token_error!("\"a\\", ErrorKind::UnterminatedString, 0, 3);
+        check_token_error!("\"\\u{12", ErrorKind::UnterminatedString, 0, 6);
+    }
+
+    #[test]
+    fn error_position_check()
+    {
+        let input = "1 print(\"a\");\n2 42 💻;";
+
+        let mut cursor = Cursor::new(input);
+        let error = loop
+        {
+            match eat(cursor)
+            {
+                Ok((_, next)) => cursor = next,
+                Err(error) => break error
+            }
+        };
+        assert_eq!(error.kind, ErrorKind::UnknownToken);
+        assert_eq!((error.line, error.column), (2, 6));
+        assert_eq!(error.to_string(), "2:6: unknown token `💻`");
+    }
+
+    #[test]
+    fn suggest_keyword_check()
+    {
+        assert_eq!(suggest_keyword("Again"), Some("again"));
+        assert_eq!(suggest_keyword("n"), Some("N"));
+        assert_eq!(suggest_keyword("PRINT"), Some("print"));
+        assert_eq!(suggest_keyword("abcd"), None);
     }
 
     #[test]
@@ -366,31 +869,53 @@ mod tests {
         // First line of fibo.wnvr
         let line =
             "1 again (1) defer (3 || N(1)<=N(2) || N(7)>99) 2#N(1),3,7;";
-        let mut input : &str = line;
 
         let expected = vec![
             "1", "again", "(", "1", ")", "defer", "(", "3", "||", "N", "(", "1",
             ")", "<=", "N", "(", "2", ")", "||", "N", "(", "7", ")", ">", "99",
             ")", "2", "#", "N", "(", "1", ")", ",", "3", ",", "7", ";"];
-        let mut actual = Vec::new();
-
-        loop
-        {
-            match eat(input)
+        let actual: Vec<_> = Lexer::new(line).map(|res| {
+            match res
             {
-                Err((error, _)) => panic!("{}. input: \"{}\"", error, input),
-                Ok((token, remainder)) =>
-                {
-                    if let TokenVariant::EOI = token.variant
-                    {
-                        break;
-                    }
-                    actual.push(token.tok);
-                    input = remainder;
-                }
+                Ok(token) => token.tok,
+                Err(error) => panic!("{}", error)
             }
-        }
+        }).collect();
 
         assert_eq!(actual, expected);
     }
+
+    #[test]
+    fn trivia_tokenization()
+    {
+        let input = "1 print(\"a\"); // b\r\n/* c */2 defer(1)\tforget 1;\n";
+        let tokens: Vec<_> = Lexer::with_trivia(input).map(|res| {
+            res.unwrap()
+        }).collect();
+
+        let rebuilt: String = tokens.iter().map(|token| token.tok).collect();
+        assert_eq!(rebuilt, input);
+        for token in &tokens
+        {
+            assert_eq!(&input[token.span.range()], token.tok);
+        }
+        let variants: Vec<_> = tokens.iter().take(10).map(|token| {
+            token.variant.clone()
+        }).collect();
+        assert_eq!(variants, vec![
+            TokenVariant::Number(Int::from(1)), TokenVariant::Whitespace,
+            TokenVariant::Print, TokenVariant::LeftParens,
+            TokenVariant::String(String::from("a")), TokenVariant::RightParens,
+            TokenVariant::Semicolon, TokenVariant::Whitespace,
+            TokenVariant::Comment, TokenVariant::Whitespace]);
+        assert_eq!(tokens[9].tok, "\r\n");
+
+        let mut lexer = Lexer::with_trivia("1 /* a");
+        assert!(matches!(lexer.next(), Some(Ok(_))));
+        assert!(matches!(lexer.next(), Some(Ok(_))));
+        let error = lexer.next().unwrap().unwrap_err();
+        assert_eq!(error.kind, ErrorKind::UnterminatedComment);
+        assert!(lexer.next().is_none());
+        assert_eq!(Lexer::new(" // a").count(), 0);
+    }
 }
@@ -1,6 +1,17 @@
+pub mod span;
+pub mod num;
+pub mod error;
 pub mod lexer;
 pub mod ast;
 pub mod parser;
+pub mod visit;
+pub mod format;
+pub mod diagnostic;
+pub mod io;
+pub mod interp;
+pub mod scheduler;
+
+pub use error::{ErrorKind, ParseError};
 
 /// Turn the input into an AST.
 ///
@@ -8,15 +19,14 @@ pub mod parser;
 ///
 /// # Errors
 ///
-/// Will return an description of the error and a slice of the input where the
-/// error happened:
+/// Will return a `ParseError` locating the error in the input:
 /// * input could not be tokenized (see `lexer::eat`)
 /// * a token that was not expected in that context was found
+/// * nodes are nested deeper than `lexer::DEFAULT_MAX_DEPTH`
 /// * an `ast::Line` could have been built but there were remaining tokens
-pub fn parse_line<'a>(line: &'a str)
-    -> Result<ast::Line<'a>, (String, &'a str)>
+pub fn parse_line<'a>(line: &'a str) -> Result<ast::Line<'a>, ParseError>
 {
-    let cursor0 = line;
+    let cursor0 = lexer::Cursor::new(line);
 
     let (line, cursor1) = parser::eat_line(cursor0)?;
 
@@ -24,6 +34,45 @@ pub fn parse_line<'a>(line: &'a str)
     match token1.variant
     {
         lexer::TokenVariant::EOI => Ok(line),
-        _ => Err((String::from("Expected end of input"), token1.tok))
+        _ =>
+        {
+            let mut error = ParseError::new(ErrorKind::TrailingInput,
+                                            cursor1.src(), token1.span);
+            error.expected = vec!["end of input"];
+            Err(error)
+        }
     }
 }
+
+/// Turn a whole program into an AST.
+///
+/// Returns the root of the AST, an `ast::Program` struct holding every line of
+/// the input in order. Lines are delimited by their `;`, not by newlines, so a
+/// single line may span several physical lines.
+///
+/// # Errors
+///
+/// Will return a `ParseError` locating the error in the input:
+/// * input could not be tokenized (see `lexer::eat`)
+/// * a token that was not expected in that context was found
+/// * nodes are nested deeper than `lexer::DEFAULT_MAX_DEPTH`
+pub fn parse_program<'a>(program: &'a str)
+    -> Result<ast::Program<'a>, ParseError>
+{
+    let (program, _) = parser::eat_program(lexer::Cursor::new(program))?;
+    Ok(program)
+}
+
+/// Turn a whole program into an AST, going on after syntax errors.
+///
+/// Unlike `parse_program`, when a line cannot be parsed, parsing resumes after
+/// the next `;` or at the next physical line starting with a number. Such
+/// lines are kept in the returned `ast::Program` as `ast::ErrorLine` nodes.
+///
+/// Returns the program and the first error found in each broken line, in
+/// order. The program is valid when there are no errors.
+pub fn parse_program_recovering<'a>(program: &'a str)
+    -> (ast::Program<'a>, Vec<ParseError>)
+{
+    parser::eat_program_recovering(lexer::Cursor::new(program))
+}
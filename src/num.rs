@@ -0,0 +1,314 @@
+#[cfg(not(feature = "bigint"))]
+use std::convert::TryFrom;
+use std::fmt;
+
+#[cfg(not(feature = "bigint"))]
+type Repr = i64;
+#[cfg(feature = "bigint")]
+type Repr = num_bigint::BigInt;
+
+/// The integers Whenever programs compute with: 64-bit signed integers, or
+/// arbitrary-precision integers with the `bigint` feature.
+#[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Int(Repr);
+
+/// What happens when a computation does not fit in an `Int`.
+///
+/// With the `bigint` feature, computations always fit, so the mode has no
+/// effect.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OverflowMode
+{
+    /// The computation fails.
+    #[default]
+    Checked,
+    /// The result wraps around at the bounds of the type.
+    Wrapping,
+    /// The result is clamped to the bounds of the type.
+    Saturating
+}
+
+/// Explains the bounds of an `Int`, for overflow errors.
+#[cfg(not(feature = "bigint"))]
+pub const OVERFLOW_HELP: Option<&str> =
+    Some("numbers are 64-bit, the `bigint` feature makes them unbounded");
+#[cfg(feature = "bigint")]
+pub const OVERFLOW_HELP: Option<&str> = None;
+
+#[cfg(not(feature = "bigint"))]
+impl Int
+{
+    /// Returns the integer written in `digits` in `base`, without a sign, or
+    /// `None` if `digits` is empty, has characters that are not digits in
+    /// `base`, or does not fit in an `Int`.
+    pub fn from_str_radix(digits: &str, base: u32) -> Option<Int>
+    {
+        if !is_digits(digits, base)
+        {
+            return None;
+        }
+        i64::from_str_radix(digits, base).ok().map(Int)
+    }
+
+    pub fn is_zero(&self) -> bool
+    {
+        self.0 == 0
+    }
+
+    pub fn is_negative(&self) -> bool
+    {
+        self.0 < 0
+    }
+
+    /// Returns the absolute value as a `usize`, if it fits.
+    pub fn abs_usize(&self) -> Option<usize>
+    {
+        usize::try_from(self.0.unsigned_abs()).ok()
+    }
+
+    /// Returns the value as a `u32`, if it fits.
+    pub fn to_u32(&self) -> Option<u32>
+    {
+        u32::try_from(self.0).ok()
+    }
+
+    /// Returns the value as a `u64`, if it fits.
+    pub fn to_u64(&self) -> Option<u64>
+    {
+        u64::try_from(self.0).ok()
+    }
+}
+
+#[cfg(feature = "bigint")]
+impl Int
+{
+    /// Returns the integer written in `digits` in `base`, without a sign, or
+    /// `None` if `digits` is empty or has characters that are not digits in
+    /// `base`.
+    pub fn from_str_radix(digits: &str, base: u32) -> Option<Int>
+    {
+        if !is_digits(digits, base)
+        {
+            return None;
+        }
+        num_bigint::BigInt::parse_bytes(digits.as_bytes(), base).map(Int)
+    }
+
+    pub fn is_zero(&self) -> bool
+    {
+        num_traits::Zero::is_zero(&self.0)
+    }
+
+    pub fn is_negative(&self) -> bool
+    {
+        num_traits::Signed::is_negative(&self.0)
+    }
+
+    /// Returns the absolute value as a `usize`, if it fits.
+    pub fn abs_usize(&self) -> Option<usize>
+    {
+        num_traits::ToPrimitive::to_usize(self.0.magnitude())
+    }
+
+    /// Returns the value as a `u32`, if it fits.
+    pub fn to_u32(&self) -> Option<u32>
+    {
+        num_traits::ToPrimitive::to_u32(&self.0)
+    }
+
+    /// Returns the value as a `u64`, if it fits.
+    pub fn to_u64(&self) -> Option<u64>
+    {
+        num_traits::ToPrimitive::to_u64(&self.0)
+    }
+}
+
+impl Int
+{
+    /// Returns the decimal integer written in `text`, with an optional sign,
+    /// or `None` if `text` is not such an integer or does not fit.
+    pub fn parse(text: &str) -> Option<Int>
+    {
+        text.parse().ok().map(Int)
+    }
+}
+
+// Whether `digits` is a non-empty run of digits in `base`, which the parsers
+// behind `from_str_radix` do not all check by themselves
+fn is_digits(digits: &str, base: u32) -> bool
+{
+    !digits.is_empty() && digits.chars().all(|c| c.is_digit(base))
+}
+
+impl From<i64> for Int
+{
+    fn from(num: i64) -> Int
+    {
+        Int(Repr::from(num))
+    }
+}
+
+impl fmt::Display for Int
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[cfg(not(feature = "bigint"))]
+impl OverflowMode
+{
+    /// Returns `a + b`, or `None` on overflow in `Checked` mode.
+    pub fn add(self, a: &Int, b: &Int) -> Option<Int>
+    {
+        match self
+        {
+            OverflowMode::Checked => a.0.checked_add(b.0),
+            OverflowMode::Wrapping => Some(a.0.wrapping_add(b.0)),
+            OverflowMode::Saturating => Some(a.0.saturating_add(b.0))
+        }.map(Int)
+    }
+
+    /// Returns `a - b`, or `None` on overflow in `Checked` mode.
+    pub fn sub(self, a: &Int, b: &Int) -> Option<Int>
+    {
+        match self
+        {
+            OverflowMode::Checked => a.0.checked_sub(b.0),
+            OverflowMode::Wrapping => Some(a.0.wrapping_sub(b.0)),
+            OverflowMode::Saturating => Some(a.0.saturating_sub(b.0))
+        }.map(Int)
+    }
+
+    /// Returns `a * b`, or `None` on overflow in `Checked` mode.
+    pub fn mul(self, a: &Int, b: &Int) -> Option<Int>
+    {
+        match self
+        {
+            OverflowMode::Checked => a.0.checked_mul(b.0),
+            OverflowMode::Wrapping => Some(a.0.wrapping_mul(b.0)),
+            OverflowMode::Saturating => Some(a.0.saturating_mul(b.0))
+        }.map(Int)
+    }
+
+    /// Returns `a / b` rounded toward zero, or `None` on overflow in
+    /// `Checked` mode.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `b` is 0.
+    pub fn div(self, a: &Int, b: &Int) -> Option<Int>
+    {
+        match self
+        {
+            OverflowMode::Checked => a.0.checked_div(b.0),
+            OverflowMode::Wrapping => Some(a.0.wrapping_div(b.0)),
+            OverflowMode::Saturating => Some(a.0.saturating_div(b.0))
+        }.map(Int)
+    }
+
+    /// Returns `-a`, or `None` on overflow in `Checked` mode.
+    pub fn neg(self, a: &Int) -> Option<Int>
+    {
+        self.sub(&Int(0), a)
+    }
+}
+
+#[cfg(feature = "bigint")]
+impl OverflowMode
+{
+    /// Returns `a + b`, which never overflows.
+    pub fn add(self, a: &Int, b: &Int) -> Option<Int>
+    {
+        Some(Int(&a.0 + &b.0))
+    }
+
+    /// Returns `a - b`, which never overflows.
+    pub fn sub(self, a: &Int, b: &Int) -> Option<Int>
+    {
+        Some(Int(&a.0 - &b.0))
+    }
+
+    /// Returns `a * b`, which never overflows.
+    pub fn mul(self, a: &Int, b: &Int) -> Option<Int>
+    {
+        Some(Int(&a.0 * &b.0))
+    }
+
+    /// Returns `a / b` rounded toward zero, which never overflows.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `b` is 0.
+    pub fn div(self, a: &Int, b: &Int) -> Option<Int>
+    {
+        Some(Int(&a.0 / &b.0))
+    }
+
+    /// Returns `-a`, which never overflows.
+    pub fn neg(self, a: &Int) -> Option<Int>
+    {
+        Some(Int(-&a.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(not(feature = "bigint"))]
+    fn overflow_mode_check()
+    {
+        let max = Int::from(i64::MAX);
+        let min = Int::from(i64::MIN);
+        let one = Int::from(1);
+
+        assert_eq!(OverflowMode::Checked.add(&max, &one), None);
+        assert_eq!(OverflowMode::Wrapping.add(&max, &one), Some(min.clone()));
+        assert_eq!(OverflowMode::Saturating.add(&max, &one), Some(max.clone()));
+        assert_eq!(OverflowMode::Checked.neg(&min), None);
+        assert_eq!(OverflowMode::Saturating.neg(&min), Some(max.clone()));
+        assert_eq!(OverflowMode::Checked.div(&min, &Int::from(-1)), None);
+        assert_eq!(OverflowMode::Checked.mul(&Int::from(-3), &Int::from(7)),
+                   Some(Int::from(-21)));
+        assert_eq!(Int::from_str_radix("9223372036854775808", 10), None);
+    }
+
+    #[test]
+    #[cfg(feature = "bigint")]
+    fn bigint_check()
+    {
+        use std::convert::TryFrom;
+
+        let max = Int::from(i64::MAX);
+        let one = Int::from(1);
+
+        let big = OverflowMode::Checked.add(&max, &one).unwrap();
+        assert_eq!(big.to_string(), "9223372036854775808");
+        assert_eq!(Int::from_str_radix("9223372036854775808", 10),
+                   Some(big.clone()));
+        assert_eq!(OverflowMode::Checked.mul(&big, &big).unwrap().to_string(),
+                   "85070591730234615865843651857942052864");
+        assert_eq!(OverflowMode::Checked.div(&Int::from(-7), &Int::from(2)),
+                   Some(Int::from(-3)));
+        assert_eq!(OverflowMode::Checked.neg(&big).unwrap().abs_usize(),
+                   usize::try_from(9223372036854775808u64).ok());
+    }
+
+    #[test]
+    fn conversion_check()
+    {
+        assert_eq!(Int::from_str_radix("ff", 16), Some(Int::from(255)));
+        assert_eq!(Int::from_str_radix("", 10), None);
+        assert_eq!(Int::from_str_radix("+1", 10), None);
+        assert_eq!(Int::from_str_radix("1_0", 10), None);
+        assert_eq!(Int::from_str_radix("19", 8), None);
+        assert_eq!(Int::parse(" 1"), None);
+        assert_eq!(Int::parse("-12"), Some(Int::from(-12)));
+        assert_eq!(Int::from(-3).abs_usize(), Some(3));
+        assert_eq!(Int::from(-3).to_u32(), None);
+        assert_eq!(Int::from(-42).to_string(), "-42");
+    }
+}
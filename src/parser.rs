@@ -1,15 +1,58 @@
 use crate::lexer;
-use crate::ast;
+use crate::ast::{self, Graph};
 
+use crate::error::{ErrorKind, ParseError};
 use crate::lexer::TokenVariant;
+use crate::span::Span;
 
 // Nomenclature:
 // tokenX: the Xth token read, 0 based
 // cursorX: the place in the input where we will get tokenX and cursorX+1 when
 //          eating
 
-pub fn eat_absnumber<'a>(input: &'a str)
-    -> Result<(ast::AbsNumber<'a>, &'a str), (String, &'a str)>
+/// Returns an `ErrorKind::UnexpectedToken` error for `token`, read at `input`.
+fn unexpected<'a>(input: lexer::Cursor<'a>, token: &lexer::Token<'a>,
+                  expected: &[&'static str]) -> ParseError
+{
+    ParseError::unexpected(input.src(), token.span, expected)
+}
+
+/// Reads a node with `eat`, one level deeper than `input`.
+///
+/// Fails with an `ErrorKind::NestingTooDeep` error instead if `input` is
+/// already at its maximum depth, so that deeply nested input cannot overflow
+/// the stack.
+fn nested<'a, T, F>(input: lexer::Cursor<'a>, eat: F)
+    -> Result<(T, lexer::Cursor<'a>), ParseError>
+    where F: FnOnce(lexer::Cursor<'a>)
+                    -> Result<(T, lexer::Cursor<'a>), ParseError>
+{
+    let depth = input.depth();
+    if depth >= input.max_depth()
+    {
+        let (token0, _) = lexer::eat(input)?;
+        return Err(ParseError::new(ErrorKind::NestingTooDeep, input.src(),
+                                   token0.span));
+    }
+    let (node, cursor1) = eat(input.with_depth(depth + 1))?;
+    Ok((node, cursor1.with_depth(depth)))
+}
+
+// Builds the terminal `ast::$type` from the lexer token `$token`
+macro_rules! terminal
+{
+    ($type: ident, $token: expr) =>
+    {
+        ast::$type { tok: $token.tok, span: $token.span }
+    };
+    ($type: ident, $token: expr, $val: expr) =>
+    {
+        ast::$type { tok: $token.tok, span: $token.span, val: $val }
+    };
+}
+
+pub fn eat_absnumber<'a>(input: lexer::Cursor<'a>)
+    -> Result<(ast::AbsNumber<'a>, lexer::Cursor<'a>), ParseError>
 {
     let cursor0 = input;
     let (token0, cursor1) = lexer::eat(cursor0)?;
@@ -17,324 +60,482 @@ pub fn eat_absnumber<'a>(input: &'a str)
     {
         TokenVariant::Number(val) =>
         {
-            let numbertok = ast::NumberToken { tok: token0.tok, val };
-            Ok((ast::AbsNumber { alt: Box::new(numbertok) }, cursor1))
+            let numbertok = terminal!(NumberToken, token0, val);
+            Ok((ast::AbsNumber::from(numbertok), cursor1))
         }
         TokenVariant::N =>
         {
-            let keywordtok = ast::NToken { tok: token0.tok };
+            let keywordtok = terminal!(NToken, token0);
             let (token1, cursor2) = lexer::eat(cursor1)?;
-            if let TokenVariant::LeftParens = token1.variant {} else
+            if !matches!(token1.variant, TokenVariant::LeftParens)
             {
-                return Err((String::from("Expected `(`"), token1.tok));
+                return Err(unexpected(cursor1, &token1, &["`(`"]));
             }
-            let lparenstok = ast::LeftParensToken { tok: token1.tok };
-            let (number, cursor3) = eat_number(cursor2)?;
+            let lparenstok = terminal!(LeftParensToken, token1);
+            let (number, cursor3) = nested(cursor2, eat_number)?;
             let (token3, cursor4) = lexer::eat(cursor3)?;
-            if let TokenVariant::RightParens = token3.variant {} else
+            if !matches!(token3.variant, TokenVariant::RightParens)
             {
-                return Err((String::from("Expected `)`"), token3.tok));
+                return Err(unexpected(cursor3, &token3, &["`)`"]));
             }
-            let rparenstok = ast::RightParensToken { tok: token3.tok };
-            let n = ast::N::new(keywordtok, lparenstok, number, rparenstok);
-            Ok((ast::AbsNumber { alt: Box::new(n) }, cursor4))
+            let rparenstok = terminal!(RightParensToken, token3);
+            let n = ast::N::new(input.src(),
+                                keywordtok,
+                                lparenstok,
+                                number,
+                                rparenstok);
+            Ok((ast::AbsNumber::from(n), cursor4))
         }
         TokenVariant::Read =>
         {
-            let keywordtok = ast::ReadToken { tok: token0.tok };
+            let keywordtok = terminal!(ReadToken, token0);
             let (token1, cursor2) = lexer::eat(cursor1)?;
-            if let TokenVariant::LeftParens = token1.variant {} else
+            if !matches!(token1.variant, TokenVariant::LeftParens)
             {
-                return Err((String::from("Expected `(`"), token1.tok));
+                return Err(unexpected(cursor1, &token1, &["`(`"]));
             }
-            let lparenstok = ast::LeftParensToken { tok: token1.tok };
+            let lparenstok = terminal!(LeftParensToken, token1);
             let (token2, cursor3) = lexer::eat(cursor2)?;
-            if let TokenVariant::RightParens = token2.variant {} else
+            if !matches!(token2.variant, TokenVariant::RightParens)
             {
-                return Err((String::from("Expected `)`"), token2.tok));
+                return Err(unexpected(cursor2, &token2, &["`)`"]));
             }
-            let rparenstok = ast::RightParensToken { tok: token2.tok };
-            let read = ast::Read::new(keywordtok, lparenstok, rparenstok);
-            Ok((ast::AbsNumber { alt: Box::new(read) }, cursor3))
+            let rparenstok = terminal!(RightParensToken, token2);
+            let read = ast::Read::new(input.src(),
+                                      keywordtok,
+                                      lparenstok,
+                                      rparenstok);
+            Ok((ast::AbsNumber::from(read), cursor3))
         }
-        _ => Err((String::from("Expected number"), token0.tok))
+        _ => Err(unexpected(cursor0, &token0, &["number"]))
     }
 }
 
-pub fn eat_number<'a>(input: &'a str)
-    -> Result<(ast::Number<'a>, &'a str), (String, &'a str)>
+// Numbers are read by precedence climbing: `eat_number` reads sums of
+// products, `eat_product` reads products of operands, and `eat_operand` reads
+// the rest (which binds tighter than any binary operator). Binary operators
+// are left associative.
+
+fn eat_operand<'a>(input: lexer::Cursor<'a>)
+    -> Result<(ast::Number<'a>, lexer::Cursor<'a>), ParseError>
 {
     let cursor0 = input;
     let (token0, cursor1) = lexer::eat(cursor0)?;
-    let cursorlast; // cursor at the end of the current number
-    let number1 : ast::Number = match token0.variant
+    match token0.variant
     {
         TokenVariant::Number(_) | TokenVariant::N | TokenVariant::Read =>
         {
             let (absnum, cursor1) = eat_absnumber(cursor0)?;
-            let absolutenumber = ast::AbsoluteNumber::new(absnum);
-            cursorlast = cursor1;
-            ast::Number { alt: Box::new(absolutenumber) }
+            let absolutenumber = ast::AbsoluteNumber::new(input.src(), absnum);
+            Ok((ast::Number::from(absolutenumber), cursor1))
         }
         TokenVariant::Plus | TokenVariant::Minus =>
         {
-            let unmathop = ast::UnMathOp { alt: match token0.variant
-                {
-                    TokenVariant::Plus =>
-                        Box::new(ast::PlusToken { tok: token0.tok }),
-                    TokenVariant::Minus =>
-                        Box::new(ast::MinusToken { tok: token0.tok }),
-                    _ => unreachable!()
-                }
+            let unmathop = match token0.variant
+            {
+                TokenVariant::Plus =>
+                    ast::UnMathOp::from(terminal!(PlusToken, token0)),
+                TokenVariant::Minus =>
+                    ast::UnMathOp::from(terminal!(MinusToken, token0)),
+                _ => unreachable!()
             };
-            let (number, cursor2) = eat_number(cursor1)?;
-            let unopnum = ast::UnOpNumber::new(unmathop, number);
-            cursorlast = cursor2;
-            ast::Number { alt: Box::new(unopnum) }
+            let (number, cursor2) = nested(cursor1, eat_operand)?;
+            let unopnum = ast::UnOpNumber::new(input.src(), unmathop, number);
+            Ok((ast::Number::from(unopnum), cursor2))
         }
         TokenVariant::LeftParens =>
         {
-            let lparenstok = ast::LeftParensToken { tok: token0.tok };
-            let (number, cursor2) = eat_number(cursor1)?;
+            let lparenstok = terminal!(LeftParensToken, token0);
+            let (number, cursor2) = nested(cursor1, eat_number)?;
             let (token2, cursor3) = lexer::eat(cursor2)?;
-            if let TokenVariant::RightParens = token2.variant {} else
+            if !matches!(token2.variant, TokenVariant::RightParens)
             {
-                return Err((String::from("Expected `)`"), token2.tok));
+                return Err(unexpected(cursor2, &token2, &["`)`"]));
             }
-            let rparenstok = ast::RightParensToken { tok: token2.tok };
-            let parensnum = ast::ParensNumber::new(lparenstok,
+            let rparenstok = terminal!(RightParensToken, token2);
+            let parensnum = ast::ParensNumber::new(input.src(),
+                                                   lparenstok,
                                                    number,
                                                    rparenstok);
-            cursorlast = cursor3;
-            ast::Number { alt: Box::new(parensnum) }
+            Ok((ast::Number::from(parensnum), cursor3))
         }
-        TokenVariant::String | TokenVariant::U =>
+        TokenVariant::String(_) | TokenVariant::U =>
         {
-            let (string, cursor1) = eat_string(cursor0)?;
+            let (string, cursor1) = eat_string_operand(cursor0)?;
             let stringtonum = ast::StringToNum { string };
-            cursorlast = cursor1;
-            ast::Number { alt: Box::new(stringtonum) }
+            Ok((ast::Number::from(stringtonum), cursor1))
         }
-        _ => { return Err((String::from("Expected number"), token0.tok)); }
-    };
+        _ => Err(unexpected(cursor0, &token0, &["number"]))
+    }
+}
 
-    let (tokenlast, cursorlastplus1) = lexer::eat(cursorlast)?;
-    match tokenlast.variant
+fn eat_product<'a>(input: lexer::Cursor<'a>)
+    -> Result<(ast::Number<'a>, lexer::Cursor<'a>), ParseError>
+{
+    let (number1, cursor1) = eat_operand(input)?;
+    eat_product_rest(number1, cursor1)
+}
+
+/// Reads the rest of a product whose first operand is `number1`.
+fn eat_product_rest<'a>(number1: ast::Number<'a>, input: lexer::Cursor<'a>)
+    -> Result<(ast::Number<'a>, lexer::Cursor<'a>), ParseError>
+{
+    let (mut number1, mut cursorlast) = (number1, input);
+    loop
     {
-        TokenVariant::Plus | TokenVariant::Minus | TokenVariant::MathOp =>
+        let (tokenlast, cursorlastplus1) = lexer::eat(cursorlast)?;
+        let val = match tokenlast.variant
         {
-            let binmathop = ast::BinMathOp { alt : match tokenlast.variant
-                {
-                    TokenVariant::Plus =>
-                        Box::new(ast::PlusToken { tok: tokenlast.tok }),
-                    TokenVariant::Minus =>
-                        Box::new(ast::MinusToken { tok: tokenlast.tok }),
-                    TokenVariant::MathOp =>
-                        Box::new(ast::MathOpToken { tok: tokenlast.tok }),
-                    _ => unreachable!()
-                }
-            };
-            let (number2, cursorlastplus2) = eat_number(cursorlastplus1)?;
-            let binopnum = ast::BinOpNumber::new(number1, binmathop, number2);
-            Ok((ast::Number { alt: Box::new(binopnum) }, cursorlastplus2))
+            TokenVariant::Star => ast::MulOp::Mul,
+            TokenVariant::Slash => ast::MulOp::Div,
+            _ => return Ok((number1, cursorlast))
+        };
+        let binmathop =
+            ast::BinMathOp::from(terminal!(MathOpToken, tokenlast, val));
+        let (number2, cursorlastplus2) = eat_operand(cursorlastplus1)?;
+        let binopnum = ast::BinOpNumber::new(input.src(),
+                                             number1,
+                                             binmathop,
+                                             number2);
+        number1 = ast::Number::from(binopnum);
+        cursorlast = cursorlastplus2;
+    }
+}
+
+/// Reads products separated by `+` and `-`.
+///
+/// If `concat` is set, the products are only separated by `-`, a `+` being
+/// left to the string that is being read.
+fn eat_sum<'a>(input: lexer::Cursor<'a>, concat: bool)
+    -> Result<(ast::Number<'a>, lexer::Cursor<'a>), ParseError>
+{
+    let (number1, cursor1) = eat_product(input)?;
+    eat_sum_rest(number1, cursor1, concat)
+}
+
+/// Reads the rest of a sum whose first product is `number1`.
+fn eat_sum_rest<'a>(number1: ast::Number<'a>, input: lexer::Cursor<'a>,
+                    concat: bool)
+    -> Result<(ast::Number<'a>, lexer::Cursor<'a>), ParseError>
+{
+    let (mut number1, mut cursorlast) = (number1, input);
+    loop
+    {
+        let (tokenlast, cursorlastplus1) = lexer::eat(cursorlast)?;
+        let binmathop = match tokenlast.variant
+        {
+            TokenVariant::Plus if !concat =>
+                ast::BinMathOp::from(terminal!(PlusToken, tokenlast)),
+            TokenVariant::Minus =>
+                ast::BinMathOp::from(terminal!(MinusToken, tokenlast)),
+            _ => return Ok((number1, cursorlast))
+        };
+        let (number2, cursorlastplus2) = eat_product(cursorlastplus1)?;
+        let binopnum = ast::BinOpNumber::new(input.src(),
+                                             number1,
+                                             binmathop,
+                                             number2);
+        number1 = ast::Number::from(binopnum);
+        cursorlast = cursorlastplus2;
+    }
+}
+
+pub fn eat_number<'a>(input: lexer::Cursor<'a>)
+    -> Result<(ast::Number<'a>, lexer::Cursor<'a>), ParseError>
+{
+    eat_sum(input, false)
+}
+
+// Booleans are read by precedence climbing too: `!` binds tighter than
+// comparisons, which bind tighter than `&&`, which binds tighter than `||`.
+// Binary operators are left associative.
+
+/// Returns whether `boolean` is a number, possibly between parentheses.
+fn is_number(boolean: &ast::Boolean) -> bool
+{
+    match &boolean.alt
+    {
+        ast::BooleanKind::NumToBool(_) => true,
+        ast::BooleanKind::Parens(parens) => is_number(&parens.boolean),
+        _ => false
+    }
+}
+
+/// Turns `boolean` back into the number it was read from.
+///
+/// `boolean` must be a number, possibly between parentheses.
+fn into_number<'a>(boolean: ast::Boolean<'a>) -> ast::Number<'a>
+{
+    match boolean.into_alt()
+    {
+        ast::BooleanKind::NumToBool(numtobool) => numtobool.num,
+        ast::BooleanKind::Parens(parens) =>
+        {
+            let ast::ParensBoolean { src, lparen, boolean, rparen, .. } =
+                *parens;
+            let number = into_number(boolean);
+            let parensnum = ast::ParensNumber::new(src, lparen, number, rparen);
+            ast::Number::from(parensnum)
         }
-        _ => Ok((number1, cursorlast))
+        _ => unreachable!()
     }
 }
 
-pub fn eat_boolean<'a>(input: &'a str)
-    -> Result<(ast::Boolean<'a>, &'a str), (String, &'a str)>
+/// Reads a parenthesized boolean, or a number converted to a boolean.
+///
+/// If `comparison` is set, also reads comparisons between numbers, including
+/// `(1 + 2) < 4` where the parentheses belong to a number.
+fn eat_bool_operand<'a>(input: lexer::Cursor<'a>, comparison: bool)
+    -> Result<(ast::Boolean<'a>, lexer::Cursor<'a>), ParseError>
 {
     let cursor0 = input;
     let (token0, cursor1) = lexer::eat(cursor0)?;
-    let cursorlast; // cursor at the end of the current boolean
-    let boolean1 : ast::Boolean = match token0.variant
+    match token0.variant
     {
-        TokenVariant::UnBoolOp =>
-        {
-            let unbooloptok = ast::UnBoolOpToken { tok: token0.tok };
-            let (boolean, cursor2) = eat_boolean(cursor1)?;
-            let unopboolean = ast::UnOpBoolean::new(unbooloptok, boolean);
-            cursorlast = cursor2;
-            ast::Boolean { alt: Box::new(unopboolean) }
-        }
         TokenVariant::LeftParens =>
         {
-            let lparenstok = ast::LeftParensToken { tok: token0.tok };
-            let (boolean, cursor2) = eat_boolean(cursor1)?;
+            let lparenstok = terminal!(LeftParensToken, token0);
+            let (boolean, cursor2) = nested(cursor1, eat_boolean)?;
             let (token2, cursor3) = lexer::eat(cursor2)?;
-            if let TokenVariant::RightParens = token2.variant {} else
+            if !matches!(token2.variant, TokenVariant::RightParens)
             {
-                return Err((String::from("Expected `)`"), token2.tok));
+                return Err(unexpected(cursor2, &token2, &["`)`"]));
             }
-            let rparenstok = ast::RightParensToken { tok: token2.tok };
-            let parensbool = ast::ParensBoolean::new(lparenstok,
+            let rparenstok = terminal!(RightParensToken, token2);
+            let parensbool = ast::ParensBoolean::new(input.src(),
+                                                     lparenstok,
                                                      boolean,
                                                      rparenstok);
-            cursorlast = cursor3;
-            ast::Boolean { alt: Box::new(parensbool) }
+            let parensbool = ast::Boolean::from(parensbool);
+
+            // The parentheses may belong to a number, which goes on after
+            // them
+            let (token3, _) = lexer::eat(cursor3)?;
+            let number_goes_on = match token3.variant
+            {
+                TokenVariant::Plus | TokenVariant::Minus
+                    | TokenVariant::Star | TokenVariant::Slash => true,
+                ref variant => comparison && variant.is_comparison()
+            };
+            if !number_goes_on || !is_number(&parensbool)
+            {
+                return Ok((parensbool, cursor3));
+            }
+            let number1 = into_number(parensbool);
+            let (number1, cursor3) = eat_product_rest(number1, cursor3)?;
+            let (number1, cursor3) = eat_sum_rest(number1, cursor3, false)?;
+            eat_comparison(number1, cursor3, comparison)
         }
         TokenVariant::Number(_) | TokenVariant::N | TokenVariant::Read
             | TokenVariant::Plus | TokenVariant::Minus
-            | TokenVariant::String | TokenVariant::U =>
+            | TokenVariant::String(_) | TokenVariant::U =>
         {
             let (number1, cursor1) = eat_number(cursor0)?;
-            let (token1, cursor2) = lexer::eat(cursor1)?;
-            match token1.variant
-            {
-                TokenVariant::BinNumBoolOp =>
-                {
-                    // number BINNUMBOOLOP number
-                    // reduce => binopnumbool
-                    let binnumbooloptok = ast::BinNumBoolOpToken {
-                        tok: token1.tok };
-                    let (number2, cursor3) = eat_number(cursor2)?;
-                    let binopnumboolean =
-                        ast::BinOpNumBoolean::new(number1,
-                                                  binnumbooloptok,
-                                                  number2);
-                    cursorlast = cursor3;
-                    ast::Boolean { alt: Box::new(binopnumboolean) }
-                }
-                _ =>
-                {
-                    // number
-                    // reduce => numtobool
-                    let numtobool = ast::NumToBool { num: number1 };
-                    cursorlast = cursor1;
-                    ast::Boolean { alt: Box::new(numtobool) }
-                }
-            }
+            eat_comparison(number1, cursor1, comparison)
         }
-        _ => { return Err((String::from("Expected boolean"), token0.tok)); }
-    };
+        _ => Err(unexpected(cursor0, &token0, &["boolean"]))
+    }
+}
+
+/// Reads the end of a comparison starting with `number1`, if `comparison` is
+/// set and there is one.
+fn eat_comparison<'a>(number1: ast::Number<'a>, input: lexer::Cursor<'a>,
+                      comparison: bool)
+    -> Result<(ast::Boolean<'a>, lexer::Cursor<'a>), ParseError>
+{
+    let cursor1 = input;
+    let (token1, cursor2) = lexer::eat(cursor1)?;
+    match token1.variant.cmp_op()
+    {
+        Some(val) if comparison =>
+        {
+            // number BINNUMBOOLOP number
+            // reduce => binopnumbool
+            let binnumbooloptok = terminal!(BinNumBoolOpToken, token1, val);
+            let (number2, cursor3) = eat_number(cursor2)?;
+            let binopnumboolean = ast::BinOpNumBoolean::new(input.src(),
+                                                            number1,
+                                                            binnumbooloptok,
+                                                            number2);
+            Ok((ast::Boolean::from(binopnumboolean), cursor3))
+        }
+        _ =>
+        {
+            // number
+            // reduce => numtobool
+            let numtobool = ast::NumToBool { num: number1 };
+            Ok((ast::Boolean::from(numtobool), cursor1))
+        }
+    }
+}
+
+/// Reads a negation, or a boolean operand if `comparison` is set.
+fn eat_negation<'a>(input: lexer::Cursor<'a>, comparison: bool)
+    -> Result<(ast::Boolean<'a>, lexer::Cursor<'a>), ParseError>
+{
+    let cursor0 = input;
+    let (token0, cursor1) = lexer::eat(cursor0)?;
+    if !matches!(token0.variant, TokenVariant::Not)
+    {
+        return eat_bool_operand(cursor0, comparison);
+    }
+    let unbooloptok = terminal!(UnBoolOpToken, token0);
+    let (boolean, cursor2) =
+        nested(cursor1, |cursor| eat_negation(cursor, false))?;
+    let unopboolean = ast::UnOpBoolean::new(input.src(), unbooloptok, boolean);
+    Ok((ast::Boolean::from(unopboolean), cursor2))
+}
 
-    let (tokenlast, cursorlastplus1) = lexer::eat(cursorlast)?;
-    match tokenlast.variant
+/// Reads booleans separated by the binary boolean operator `op`, reading
+/// them with `eat_operand`.
+fn eat_binopboolean<'a, F>(input: lexer::Cursor<'a>, op: ast::BoolOp,
+                           eat_operand: F)
+    -> Result<(ast::Boolean<'a>, lexer::Cursor<'a>), ParseError>
+    where F: Fn(lexer::Cursor<'a>)
+        -> Result<(ast::Boolean<'a>, lexer::Cursor<'a>), ParseError>
+{
+    let (mut boolean1, mut cursorlast) = eat_operand(input)?;
+    loop
     {
-        TokenVariant::BinBoolOp =>
+        let (tokenlast, cursorlastplus1) = lexer::eat(cursorlast)?;
+        let val = match tokenlast.variant
         {
-            let binbooloptok = ast::BinBoolOpToken { tok: tokenlast.tok };
-            let (boolean2, cursorlastplus2) = eat_boolean(cursorlastplus1)?;
-            let binopboolean = ast::BinOpBoolean::new(boolean1,
-                                                      binbooloptok,
-                                                      boolean2);
-            Ok((ast::Boolean { alt: Box::new(binopboolean) }, cursorlastplus2))
+            TokenVariant::And => ast::BoolOp::And,
+            TokenVariant::Or => ast::BoolOp::Or,
+            _ => return Ok((boolean1, cursorlast))
+        };
+        if val != op
+        {
+            return Ok((boolean1, cursorlast));
         }
-        _ => Ok((boolean1, cursorlast))
+        let binbooloptok = terminal!(BinBoolOpToken, tokenlast, val);
+        let (boolean2, cursorlastplus2) = eat_operand(cursorlastplus1)?;
+        let binopboolean = ast::BinOpBoolean::new(input.src(),
+                                                  boolean1,
+                                                  binbooloptok,
+                                                  boolean2);
+        boolean1 = ast::Boolean::from(binopboolean);
+        cursorlast = cursorlastplus2;
     }
 }
 
-pub fn eat_string<'a>(input: &'a str)
-    -> Result<(ast::String_<'a>, &'a str), (String, &'a str)>
+pub fn eat_boolean<'a>(input: lexer::Cursor<'a>)
+    -> Result<(ast::Boolean<'a>, lexer::Cursor<'a>), ParseError>
+{
+    let eat_not = |cursor| eat_negation(cursor, true);
+    let eat_and = |cursor| {
+        eat_binopboolean(cursor, ast::BoolOp::And, eat_not)
+    };
+    eat_binopboolean(input, ast::BoolOp::Or, eat_and)
+}
+
+// Strings are concatenations of operands, read left to right. A `+` in a
+// string is a concatenation, so numbers that follow one are read up to the
+// next `+`: `"a" + 1 + 2` is `"a12"`, while `"a" + 3 - 1 * 2` is `"a1"`. A
+// string starting with a number is read as a number first, so that
+// `1 + 2 + "a"` is `"3a"`.
+
+fn eat_string_operand<'a>(input: lexer::Cursor<'a>)
+    -> Result<(ast::String_<'a>, lexer::Cursor<'a>), ParseError>
 {
     let cursor0 = input;
     let (token0, cursor1) = lexer::eat(cursor0)?;
-    let cursorlast; // cursor at the end of the current string
-    let string1 : ast::String_<'a> = match token0.variant
+    match token0.variant
     {
-        TokenVariant::String =>
+        TokenVariant::String(val) =>
         {
-            let stringtoken = ast::StringToken { tok: token0.tok };
-            cursorlast = cursor1;
-            ast::String_ { alt: Box::new(stringtoken) }
+            let stringtoken = terminal!(StringToken, token0, val);
+            Ok((ast::String_::from(stringtoken), cursor1))
         }
         TokenVariant::U =>
         {
-            let keywordtok = ast::UToken { tok: token0.tok };
+            let keywordtok = terminal!(UToken, token0);
             let (token1, cursor2) = lexer::eat(cursor1)?;
-            if let TokenVariant::LeftParens = token1.variant {} else
+            if !matches!(token1.variant, TokenVariant::LeftParens)
             {
-                return Err((String::from("Expected `(`"), token1.tok));
+                return Err(unexpected(cursor1, &token1, &["`(`"]));
             }
-            let lparenstok = ast::LeftParensToken { tok: token1.tok };
+            let lparenstok = terminal!(LeftParensToken, token1);
             let (number, cursor3) = eat_absnumber(cursor2)?;
             let (token3, cursor4) = lexer::eat(cursor3)?;
-            if let TokenVariant::RightParens = token3.variant {} else
+            if !matches!(token3.variant, TokenVariant::RightParens)
             {
-                return Err((String::from("Expected `)`"), token3.tok));
+                return Err(unexpected(cursor3, &token3, &["`)`"]));
             }
-            let rparenstok = ast::RightParensToken { tok: token3.tok };
-            let u = ast::U::new(keywordtok, lparenstok, number, rparenstok);
-            cursorlast = cursor4;
-            ast::String_ { alt: Box::new(u) }
+            let rparenstok = terminal!(RightParensToken, token3);
+            let u = ast::U::new(input.src(),
+                                keywordtok,
+                                lparenstok,
+                                number,
+                                rparenstok);
+            Ok((ast::String_::from(u), cursor4))
         }
+        TokenVariant::Number(_) | TokenVariant::N | TokenVariant::Read
+            | TokenVariant::Plus | TokenVariant::Minus
+            | TokenVariant::LeftParens =>
+        {
+            let (number, cursor1) = eat_sum(cursor0, true)?;
+            let numtostring = ast::NumToString { num: number };
+            Ok((ast::String_::from(numtostring), cursor1))
+        }
+        _ => Err(unexpected(cursor0, &token0, &["string"]))
+    }
+}
+
+pub fn eat_string<'a>(input: lexer::Cursor<'a>)
+    -> Result<(ast::String_<'a>, lexer::Cursor<'a>), ParseError>
+{
+    let cursor0 = input;
+    let (token0, _) = lexer::eat(cursor0)?;
+    let (mut string1, mut cursorlast) = match token0.variant
+    {
         TokenVariant::Number(_) | TokenVariant::N | TokenVariant::Read
             | TokenVariant::Plus | TokenVariant::Minus
             | TokenVariant::LeftParens =>
         {
             let (number, cursor1) = eat_number(cursor0)?;
             let numtostring = ast::NumToString { num: number };
-            cursorlast = cursor1;
-            ast::String_ { alt: Box::new(numtostring) }
+            (ast::String_::from(numtostring), cursor1)
         }
-        _ => { return Err((String::from("Expected string"), token0.tok)); }
+        _ => eat_string_operand(cursor0)?
     };
-
-    let (tokenlast, cursorlastplus1) = lexer::eat(cursorlast)?;
-    match tokenlast.variant
+    loop
     {
-        TokenVariant::Plus =>
+        let (tokenlast, cursorlastplus1) = lexer::eat(cursorlast)?;
+        if !matches!(tokenlast.variant, TokenVariant::Plus)
         {
-            let plustoken = ast::PlusToken { tok: tokenlast.tok };
-            let (string2, cursorlastplus2) = eat_string(cursorlastplus1)?;
-            let concat = ast::Concat::new(string1, plustoken, string2);
-            Ok((ast::String_ { alt: Box::new(concat) }, cursorlastplus2))
+            return Ok((string1, cursorlast));
         }
-        _ => Ok((string1, cursorlast))
+        let plustoken = terminal!(PlusToken, tokenlast);
+        let (string2, cursorlastplus2) = eat_string_operand(cursorlastplus1)?;
+        let concat = ast::Concat::new(input.src(), string1, plustoken, string2);
+        string1 = ast::String_::from(concat);
+        cursorlast = cursorlastplus2;
     }
 }
 
-pub fn eat_lineops<'a>(input: &'a str)
-    -> Result<(ast::LineOps<'a>, &'a str), (String, &'a str)>
+/// Reads a single line operation.
+fn eat_lineop<'a>(input: lexer::Cursor<'a>)
+    -> Result<(ast::LineOp<'a>, lexer::Cursor<'a>), ParseError>
 {
     let cursor0 = input;
     let (number, cursor1) = eat_number(cursor0)?;
     let (token1, cursor2) = lexer::eat(cursor1)?;
     match token1.variant
     {
-        TokenVariant::Comma =>
-        {
-            // number COMMA lineops
-            // reduce => numtolineop COMMA lineops
-            // reduce => lineop COMMA lineops
-            // reduce => lineoplist
-            let numtolineop = ast::NumToLineOp { num: number };
-            let lineop = ast::LineOp::new(ast::SingleLineOp {
-                alt: Box::new(numtolineop)
-            });
-            let commatok = ast::CommaToken { tok: token1.tok };
-            let (lineops, cursor3) = eat_lineops(cursor2)?;
-            let lineoplist = ast::LineOpList::new(lineop, commatok, lineops);
-            Ok((ast::LineOps { alt: Box::new(lineoplist) }, cursor3))
-        }
         TokenVariant::Sharp =>
         {
             // number SHARP number
             // reduce => countlineop
             // reduce => lineop
-            let sharptok = ast::SharpToken { tok: token1.tok };
+            let sharptok = terminal!(SharpToken, token1);
             let (count, cursor3) = eat_number(cursor2)?;
-            let countlineop = ast::CountLineOp::new(number, sharptok, count);
-            let lineop = ast::LineOp::new(ast::SingleLineOp {
-                alt: Box::new(countlineop) });
-
-            let (token3, cursor4) = lexer::eat(cursor3)?;
-            match token3.variant
-            {
-                TokenVariant::Comma =>
-                {
-                    // lineop COMMA lineops
-                    // reduce => lineoplist
-                    let commatok = ast::CommaToken { tok: token3.tok };
-                    let (lineops, cursor5) = eat_lineops(cursor4)?;
-                    let lineoplist = ast::LineOpList::new(lineop,
-                                                          commatok,
-                                                          lineops);
-                    Ok((ast::LineOps { alt: Box::new(lineoplist) }, cursor5))
-                }
-                _ => Ok((ast::LineOps { alt: Box::new(lineop) }, cursor3))
-            }
+            let countlineop = ast::CountLineOp::new(input.src(),
+                                                    number,
+                                                    sharptok,
+                                                    count);
+            let slo = ast::SingleLineOp::from(countlineop);
+            Ok((ast::LineOp::new(input.src(), slo), cursor3))
         }
         _ =>
         {
@@ -342,15 +543,49 @@ pub fn eat_lineops<'a>(input: &'a str)
             // reduce => numtolineop
             // reduce => lineop
             let numtolineop = ast::NumToLineOp { num: number };
-            let lineop = ast::LineOp::new(ast::SingleLineOp {
-                alt: Box::new(numtolineop) });
-            Ok((ast::LineOps { alt: Box::new(lineop) }, cursor1))
+            let slo = ast::SingleLineOp::from(numtolineop);
+            Ok((ast::LineOp::new(input.src(), slo), cursor1))
         }
     }
 }
 
-pub fn eat_statement<'a>(input: &'a str)
-    -> Result<(ast::Statement<'a>, &'a str), (String, &'a str)>
+pub fn eat_lineops<'a>(input: lexer::Cursor<'a>)
+    -> Result<(ast::LineOps<'a>, lexer::Cursor<'a>), ParseError>
+{
+    // Lists are read in a loop rather than recursively, so that long ones do
+    // not overflow the stack
+    let (lineop, mut cursorlast) = eat_lineop(input)?;
+    let mut lineops = vec![lineop];
+    let mut commas = Vec::new();
+    loop
+    {
+        let (tokenlast, cursorlastplus1) = lexer::eat(cursorlast)?;
+        if !matches!(tokenlast.variant, TokenVariant::Comma)
+        {
+            break;
+        }
+        commas.push(terminal!(CommaToken, tokenlast));
+        let (lineop, cursorlastplus2) = eat_lineop(cursorlastplus1)?;
+        lineops.push(lineop);
+        cursorlast = cursorlastplus2;
+    }
+
+    // lineop COMMA lineops
+    // reduce => lineoplist
+    let mut lineops1 = ast::LineOps::from(lineops.pop().unwrap());
+    for (lineop, commatok) in lineops.into_iter().zip(commas).rev()
+    {
+        let lineoplist = ast::LineOpList::new(input.src(),
+                                              lineop,
+                                              commatok,
+                                              lineops1);
+        lineops1 = ast::LineOps::from(lineoplist);
+    }
+    Ok((lineops1, cursorlast))
+}
+
+pub fn eat_statement<'a>(input: lexer::Cursor<'a>)
+    -> Result<(ast::Statement<'a>, lexer::Cursor<'a>), ParseError>
 {
     let cursor0 = input;
     let (token0, cursor1) = lexer::eat(cursor0)?;
@@ -358,113 +593,117 @@ pub fn eat_statement<'a>(input: &'a str)
     {
         TokenVariant::Again =>
         {
-            let keywordtok = ast::AgainToken { tok: token0.tok };
+            let keywordtok = terminal!(AgainToken, token0);
             let (token1, cursor2) = lexer::eat(cursor1)?;
-            if let TokenVariant::LeftParens = token1.variant {} else
+            if !matches!(token1.variant, TokenVariant::LeftParens)
             {
-                return Err((String::from("Expected `(`"), token1.tok));
+                return Err(unexpected(cursor1, &token1, &["`(`"]));
             }
-            let lparenstok = ast::LeftParensToken { tok: token1.tok };
+            let lparenstok = terminal!(LeftParensToken, token1);
             let (boolean, cursor3) = eat_boolean(cursor2)?;
             let (token3, cursor4) = lexer::eat(cursor3)?;
-            if let TokenVariant::RightParens = token3.variant {} else
+            if !matches!(token3.variant, TokenVariant::RightParens)
             {
-                return Err((String::from("Expected `)`"), token3.tok));
+                return Err(unexpected(cursor3, &token3, &["`)`"]));
             }
-            let rparenstok = ast::RightParensToken { tok: token3.tok };
-            let (statement, cursor5) = eat_statement(cursor4)?;
-            let again = ast::Again::new(keywordtok,
+            let rparenstok = terminal!(RightParensToken, token3);
+            let (statement, cursor5) = nested(cursor4, eat_statement)?;
+            let again = ast::Again::new(input.src(),
+                                        keywordtok,
                                         lparenstok,
                                         boolean,
                                         rparenstok,
                                         statement);
-            Ok((ast::Statement { alt: Box::new(again) }, cursor5))
+            Ok((ast::Statement::from(again), cursor5))
         }
         TokenVariant::Defer =>
         {
-            let keywordtok = ast::DeferToken { tok: token0.tok };
+            let keywordtok = terminal!(DeferToken, token0);
             let (token1, cursor2) = lexer::eat(cursor1)?;
-            if let TokenVariant::LeftParens = token1.variant {} else
+            if !matches!(token1.variant, TokenVariant::LeftParens)
             {
-                return Err((String::from("Expected `(`"), token1.tok));
+                return Err(unexpected(cursor1, &token1, &["`(`"]));
             }
-            let lparenstok = ast::LeftParensToken { tok: token1.tok };
+            let lparenstok = terminal!(LeftParensToken, token1);
             let (boolean, cursor3) = eat_boolean(cursor2)?;
             let (token3, cursor4) = lexer::eat(cursor3)?;
-            if let TokenVariant::RightParens = token3.variant {} else
+            if !matches!(token3.variant, TokenVariant::RightParens)
             {
-                return Err((String::from("Expected `)`"), token3.tok));
+                return Err(unexpected(cursor3, &token3, &["`)`"]));
             }
-            let rparenstok = ast::RightParensToken { tok: token3.tok };
-            let (statement, cursor5) = eat_statement(cursor4)?;
-            let defer = ast::Defer::new(keywordtok,
+            let rparenstok = terminal!(RightParensToken, token3);
+            let (statement, cursor5) = nested(cursor4, eat_statement)?;
+            let defer = ast::Defer::new(input.src(),
+                                        keywordtok,
                                         lparenstok,
                                         boolean,
                                         rparenstok,
                                         statement);
-            Ok((ast::Statement { alt: Box::new(defer) }, cursor5))
+            Ok((ast::Statement::from(defer), cursor5))
         }
         TokenVariant::Forget =>
         {
-            let keywordtok = ast::ForgetToken { tok: token0.tok };
+            let keywordtok = terminal!(ForgetToken, token0);
             let (token1, cursor2) = lexer::eat(cursor1)?;
-            if let TokenVariant::LeftParens = token1.variant {} else
+            if !matches!(token1.variant, TokenVariant::LeftParens)
             {
-                return Err((String::from("Expected `(`"), token1.tok));
+                return Err(unexpected(cursor1, &token1, &["`(`"]));
             }
-            let lparenstok = ast::LeftParensToken { tok: token1.tok };
+            let lparenstok = terminal!(LeftParensToken, token1);
             let (boolean, cursor3) = eat_boolean(cursor2)?;
             let (token3, cursor4) = lexer::eat(cursor3)?;
-            if let TokenVariant::RightParens = token3.variant {} else
+            if !matches!(token3.variant, TokenVariant::RightParens)
             {
-                return Err((String::from("Expected `)`"), token3.tok));
+                return Err(unexpected(cursor3, &token3, &["`)`"]));
             }
-            let rparenstok = ast::RightParensToken { tok: token3.tok };
-            let (statement, cursor5) = eat_statement(cursor4)?;
-            let forget = ast::Forget::new(keywordtok,
+            let rparenstok = terminal!(RightParensToken, token3);
+            let (statement, cursor5) = nested(cursor4, eat_statement)?;
+            let forget = ast::Forget::new(input.src(),
+                                          keywordtok,
                                           lparenstok,
                                           boolean,
                                           rparenstok,
                                           statement);
-            Ok((ast::Statement { alt: Box::new(forget) }, cursor5))
+            Ok((ast::Statement::from(forget), cursor5))
         }
         TokenVariant::Print =>
         {
-            let keywordtok = ast::PrintToken { tok: token0.tok };
+            let keywordtok = terminal!(PrintToken, token0);
             let (token1, cursor2) = lexer::eat(cursor1)?;
-            if let TokenVariant::LeftParens = token1.variant {} else
+            if !matches!(token1.variant, TokenVariant::LeftParens)
             {
-                return Err((String::from("Expected `(`"), token1.tok));
+                return Err(unexpected(cursor1, &token1, &["`(`"]));
             }
-            let lparenstok = ast::LeftParensToken { tok: token1.tok };
+            let lparenstok = terminal!(LeftParensToken, token1);
             let (string, cursor3) = eat_string(cursor2)?;
             let (token3, cursor4) = lexer::eat(cursor3)?;
-            if let TokenVariant::RightParens = token3.variant {} else
+            if !matches!(token3.variant, TokenVariant::RightParens)
             {
-                return Err((String::from("Expected `)`"), token3.tok));
+                return Err(unexpected(cursor3, &token3, &["`)`"]));
             }
-            let rparenstok = ast::RightParensToken { tok: token3.tok };
-            let print = ast::Print::new(keywordtok,
+            let rparenstok = terminal!(RightParensToken, token3);
+            let print = ast::Print::new(input.src(),
+                                        keywordtok,
                                         lparenstok,
                                         string,
                                         rparenstok);
-            Ok((ast::Statement { alt: Box::new(print) }, cursor4))
+            Ok((ast::Statement::from(print), cursor4))
         }
         TokenVariant::Number(_) | TokenVariant::N | TokenVariant::Read
             | TokenVariant::Plus | TokenVariant::Minus
-            | TokenVariant::LeftParens | TokenVariant::String
+            | TokenVariant::LeftParens | TokenVariant::String(_)
             | TokenVariant::U =>
         {
             let (lineops, cursor1) = eat_lineops(cursor0)?;
-            let lineoperations = ast::LineOperations::new(lineops);
-            Ok((ast::Statement { alt: Box::new(lineoperations) }, cursor1))
+            let lineoperations = ast::LineOperations::new(input.src(), lineops);
+            Ok((ast::Statement::from(lineoperations), cursor1))
         }
-        _ => Err((String::from("Expected statement"), token0.tok))
+        _ => Err(unexpected(cursor0, &token0, &["statement"]))
     }
 }
 
-pub fn eat_line<'a>(input: &'a str)
-    -> Result<(ast::Line<'a>, &'a str), (String, &'a str)>
+pub fn eat_line<'a>(input: lexer::Cursor<'a>)
+    -> Result<(ast::Line<'a>, lexer::Cursor<'a>), ParseError>
 {
     let cursor0 = input;
 
@@ -472,42 +711,485 @@ pub fn eat_line<'a>(input: &'a str)
     let lineno;
     if let TokenVariant::Number(val) = token0.variant
     {
-        lineno = ast::NumberToken { tok: token0.tok, val };
+        lineno = terminal!(NumberToken, token0, val);
     }
     else
     {
-        return Err((String::from("Expected number"), token0.tok));
+        return Err(unexpected(cursor0, &token0, &["number"]));
     }
 
     let (statement, cursor2) = eat_statement(cursor1)?;
 
     let (token2, cursor3) = lexer::eat(cursor2)?;
-    if let TokenVariant::Semicolon = token2.variant {} else
+    if !matches!(token2.variant, TokenVariant::Semicolon)
+    {
+        return Err(unexpected(cursor2, &token2, &["`;`"]));
+    }
+    let semicolontok = terminal!(SemicolonToken, token2);
+
+    Ok((ast::Line::new(input.src(), lineno, statement, semicolontok), cursor3))
+}
+
+pub fn eat_program<'a>(input: lexer::Cursor<'a>)
+    -> Result<(ast::Program<'a>, lexer::Cursor<'a>), ParseError>
+{
+    let mut cursor = input;
+    let mut lines = Vec::new();
+
+    loop
+    {
+        let (token, cursornext) = lexer::eat(cursor)?;
+        if let TokenVariant::EOI = token.variant
+        {
+            let comments = eat_comments(input, &lines);
+            let span = input.span(input.rest().len());
+            let program = ast::Program { src: input.src(), span, lines,
+                                         comments };
+            return Ok((program, cursornext));
+        }
+        let (line, cursornext) = eat_line(cursor)?;
+        lines.push(ast::ProgramLine::Line(line));
+        cursor = cursornext;
+    }
+}
+
+/// Reads the comments from `input` to the end of input, and attaches them to
+/// the `lines` they precede.
+///
+/// Parts that cannot be tokenized are skipped.
+fn eat_comments<'a>(input: lexer::Cursor<'a>, lines: &[ast::ProgramLine<'a>])
+    -> Vec<ast::Comment<'a>>
+{
+    let starts: Vec<usize> = lines.iter().map(|line| {
+        line.span().start
+    }).collect();
+
+    let mut comments = Vec::new();
+    let mut cursor = input;
+    loop
     {
-        return Err((String::from("Expected `;`"), token2.tok));
+        let (tokens, cursornext) = match lexer::eat_trivia(cursor)
+        {
+            Ok(trivia) => trivia,
+            // Unterminated comment, up to the end of input
+            Err(_) => return comments
+        };
+        for token in tokens.into_iter().filter(|token| {
+            token.variant == TokenVariant::Comment
+        })
+        {
+            let line = starts.iter().position(|&start| {
+                token.span.end <= start
+            });
+            comments.push(ast::Comment { tok: token.tok, span: token.span,
+                                         line });
+        }
+        cursor = match lexer::eat(cursornext)
+        {
+            Ok((token, _)) if matches!(token.variant, TokenVariant::EOI) =>
+                return comments,
+            Ok((_, cursornext)) => cursornext,
+            // Skip the part that could not be tokenized
+            Err(lexerror) =>
+                cursornext.advance(lexerror.span.end - cursornext.pos())
+        };
     }
-    let semicolontok = ast::SemicolonToken { tok: token2.tok };
+}
 
-    Ok((ast::Line::new(lineno, statement, semicolontok), cursor3))
+/// Skips the input after `error` until a line may start.
+///
+/// Parsing may resume after the next `;`, or before the next number that
+/// starts a physical line (in case the `;` was forgotten). `input` is the
+/// cursor at the start of the line where `error` happened.
+fn recover<'a>(input: lexer::Cursor<'a>, error: &ParseError)
+    -> lexer::Cursor<'a>
+{
+    let mut cursor = input;
+    loop
+    {
+        match lexer::eat(cursor)
+        {
+            Ok((token, cursornext)) =>
+            {
+                let past_error = error.span.start <= token.span.start;
+                match token.variant
+                {
+                    TokenVariant::EOI => return cursornext,
+                    TokenVariant::Semicolon if past_error => return cursornext,
+                    TokenVariant::Number(_) if past_error
+                        && input.pos() < cursor.pos()
+                        && input.src()[cursor.pos()..token.span.start]
+                            .contains('\n') => return cursor,
+                    _ => cursor = cursornext
+                }
+            }
+            Err(lexerror) =>
+            {
+                // Skip the part that could not be tokenized
+                cursor = cursor.advance(lexerror.span.end - cursor.pos());
+            }
+        }
+    }
+}
+
+/// Reads a whole program, replacing the lines that could not be parsed with
+/// `ast::ErrorLine` nodes.
+///
+/// Returns the program and the first error of each line that could not be
+/// parsed.
+pub fn eat_program_recovering<'a>(input: lexer::Cursor<'a>)
+    -> (ast::Program<'a>, Vec<ParseError>)
+{
+    let mut cursor = input;
+    let mut lines = Vec::new();
+    let mut errors = Vec::new();
+
+    loop
+    {
+        let start = match lexer::eat(cursor)
+        {
+            Ok((token, _)) if matches!(token.variant, TokenVariant::EOI) =>
+            {
+                let comments = eat_comments(input, &lines);
+                let span = input.span(input.rest().len());
+                let program = ast::Program { src: input.src(), span, lines,
+                                             comments };
+                return (program, errors);
+            }
+            Ok((token, _)) => token.span.start,
+            Err(error) => error.span.start
+        };
+        match eat_line(cursor)
+        {
+            Ok((line, cursornext)) =>
+            {
+                lines.push(ast::ProgramLine::Line(line));
+                cursor = cursornext;
+            }
+            Err(error) =>
+            {
+                let cursornext = recover(cursor, &error);
+                let range = input.src()[start..cursornext.pos()].trim_end();
+                let span = Span::new(start, start + range.len());
+                let errorline = ast::ErrorLine { src: input.src(), span };
+                lines.push(ast::ProgramLine::Error(errorline));
+                errors.push(error);
+                cursor = cursornext;
+            }
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    use crate::error::ErrorKind;
+    use crate::span::Span;
+
     #[test]
     fn absnumber_check()
     {
         let input = "123abc";
 
-        match eat_absnumber(input)
+        match eat_absnumber(lexer::Cursor::new(input))
         {
             Ok((root, cursor)) =>
             {
                 assert_eq!(root.alt.get_str(), &input[..3]);
-                assert_eq!(cursor, &input[3..]);
+                assert_eq!(cursor.rest(), &input[3..]);
+            }
+            Err(error) => panic!("{}", error)
+        }
+    }
+
+    #[test]
+    fn program_check()
+    {
+        let input = "1 2;\n2 defer (1)\n  print(\"two\");\n";
+
+        match eat_program(lexer::Cursor::new(input))
+        {
+            Ok((root, cursor)) =>
+            {
+                assert_eq!(root.lines.len(), 2);
+                assert_eq!(root.lines[0].get_str(), "1 2;");
+                assert_eq!(root.lines[1].get_str(),
+                           "2 defer (1)\n  print(\"two\");");
+                assert_eq!(root.lines[1].span(), Span::new(5, 32));
+                assert_eq!(root.span, Span::new(0, 33));
+                assert_eq!(cursor.rest(), "");
+            }
+            Err(error) => panic!("{}", error)
+        }
+    }
+
+    #[test]
+    fn span_check()
+    {
+        let input = "  1 (2 + 3)#4 ;";
+        let (line, _) = eat_line(lexer::Cursor::new(input)).unwrap();
+        assert_eq!(line.span, Span::new(2, 15));
+        assert_eq!(line.num.span, Span::new(2, 3));
+        let lineops = match line.stmt.alt
+        {
+            ast::StatementKind::LineOperations(lineops) => lineops,
+            _ => panic!("Not line operations")
+        };
+        assert_eq!(lineops.span, Span::new(4, 13));
+        assert_eq!(lineops.get_str(), "(2 + 3)#4");
+        match &lineops.lineops.alt
+        {
+            ast::LineOpsKind::LineOp(lineop) => match &lineop.slo.alt
+            {
+                ast::SingleLineOpKind::Count(count) =>
+                {
+                    assert_eq!(count.line.alt.span(), Span::new(4, 11));
+                    assert_eq!(count.sharp.span, Span::new(11, 12));
+                    assert_eq!(count.count.alt.get_str(), "4");
+                }
+                _ => panic!("Not a count")
+            },
+            _ => panic!("Not a single line operation")
+        }
+    }
+
+    #[test]
+    fn error_check()
+    {
+        let input = "1 2;\n2 again 1) 3;";
+
+        match eat_program(lexer::Cursor::new(input))
+        {
+            Ok(_) => panic!("Not an error"),
+            Err(error) =>
+            {
+                assert_eq!(error.kind, ErrorKind::UnexpectedToken);
+                assert_eq!(error.span, Span::new(13, 14));
+                assert_eq!((error.line, error.column), (2, 9));
+                assert_eq!(error.expected, vec!["`(`"]);
+                assert_eq!(error.to_string(), "2:9: expected `(`, found `1`");
+            }
+        }
+    }
+
+    #[test]
+    fn recovery_check()
+    {
+        let input = "1 again 1) 3;\n2 print(\"ok\");\n3 4 5\n\
+                     4 defer (1) $;\n5 6;";
+
+        let (program, errors) =
+            eat_program_recovering(lexer::Cursor::new(input));
+        let lines: Vec<_> = program.lines.iter().map(|line| {
+            (line.as_line().is_some(), line.get_str())
+        }).collect();
+        assert_eq!(lines, vec![(false, "1 again 1) 3;"),
+                               (true, "2 print(\"ok\");"),
+                               (false, "3 4 5"),
+                               (false, "4 defer (1) $;"),
+                               (true, "5 6;")]);
+        let errors: Vec<_> = errors.iter().map(|error| {
+            (error.kind, error.line)
+        }).collect();
+        assert_eq!(errors, vec![(ErrorKind::UnexpectedToken, 1),
+                                (ErrorKind::UnexpectedToken, 3),
+                                (ErrorKind::UnknownToken, 4)]);
+    }
+
+    #[test]
+    fn comments_check()
+    {
+        let input = "// Prints twice\n1 print(\"a\"); // to line 2\n\
+                     2 /* again */ 1;\n/* end */";
+
+        let (program, _) = eat_program(lexer::Cursor::new(input)).unwrap();
+        assert_eq!(program.lines.len(), 2);
+        let comments: Vec<_> = program.comments.iter().map(|comment| {
+            (comment.tok, comment.line)
+        }).collect();
+        assert_eq!(comments, vec![("// Prints twice", Some(0)),
+                                  ("// to line 2", Some(1)),
+                                  ("/* again */", None),
+                                  ("/* end */", None)]);
+        assert_eq!(program.comments[1].span, Span::new(30, 42));
+
+        let input = "1 $ 2; // a\n2 3;";
+        let (program, errors) =
+            eat_program_recovering(lexer::Cursor::new(input));
+        assert_eq!(errors.len(), 1);
+        assert_eq!(program.comments[0].tok, "// a");
+        assert_eq!(program.comments[0].line, Some(1));
+    }
+
+    #[test]
+    fn operator_check()
+    {
+        let comparisons = [("<", ast::CmpOp::Lt), ("<=", ast::CmpOp::Le),
+                           (">", ast::CmpOp::Gt), (">=", ast::CmpOp::Ge),
+                           ("==", ast::CmpOp::Eq), ("!=", ast::CmpOp::Ne)];
+        for &(tok, val) in comparisons.iter()
+        {
+            let input = format!("1 {} 2", tok);
+            let (boolean, _) = eat_boolean(lexer::Cursor::new(&input)).unwrap();
+            match &boolean.alt
+            {
+                ast::BooleanKind::BinOpNum(binop) =>
+                {
+                    assert_eq!(binop.op.tok, tok);
+                    assert_eq!(binop.op.val, val);
+                }
+                _ => panic!("Not a comparison")
+            }
+        }
+
+        let (number, _) = eat_number(lexer::Cursor::new("6 / 3 * 2")).unwrap();
+        match &number.alt
+        {
+            ast::NumberKind::BinOp(binop) =>
+            {
+                match &binop.op.alt
+                {
+                    ast::BinMathOpKind::MathOp(op) =>
+                        assert_eq!(op.val, ast::MulOp::Mul),
+                    _ => panic!("Not a multiplication")
+                }
+                match &binop.num1.alt
+                {
+                    ast::NumberKind::BinOp(binop) => match &binop.op.alt
+                    {
+                        ast::BinMathOpKind::MathOp(op) =>
+                            assert_eq!(op.val, ast::MulOp::Div),
+                        _ => panic!("Not a division")
+                    },
+                    _ => panic!("Not a binary operation")
+                }
             }
-            Err((error, at)) => panic!("{}: {}", error, at)
+            _ => panic!("Not a binary operation")
+        }
+    }
+
+    #[test]
+    fn parenthesized_number_check()
+    {
+        let (boolean, _) =
+            eat_boolean(lexer::Cursor::new("(1 + 2) * 3 < 4")).unwrap();
+        match &boolean.alt
+        {
+            ast::BooleanKind::BinOpNum(binop) =>
+                assert_eq!(binop.num1.alt.get_str(), "(1 + 2) * 3"),
+            _ => panic!("Not a comparison")
+        }
+
+        let inputs = ["((1)) + 2", "!(1) - 2"];
+        for input in inputs.iter()
+        {
+            let (boolean, cursor) =
+                eat_boolean(lexer::Cursor::new(input)).unwrap();
+            let boolean = match &boolean.alt
+            {
+                ast::BooleanKind::UnOp(unop) => &unop.boolean,
+                _ => &boolean
+            };
+            match &boolean.alt
+            {
+                ast::BooleanKind::NumToBool(numtobool) =>
+                    assert!(matches!(numtobool.num.alt,
+                                     ast::NumberKind::BinOp(_))),
+                _ => panic!("Not a number in {}", input)
+            }
+            assert_eq!(cursor.rest(), "");
+        }
+
+        // The parentheses are read once, and errors in them are reported
+        let input = "((1 +) < 2)";
+        let error = eat_boolean(lexer::Cursor::new(input)).err().unwrap();
+        assert_eq!(error.span, Span::new(5, 6));
+        assert_eq!(error.expected, vec!["number"]);
+    }
+
+    #[test]
+    fn statement_kind_check()
+    {
+        let input = "1 again (1) defer (3 || N(1)<=N(2)) print(N(1));";
+
+        let (line, _) = eat_line(lexer::Cursor::new(input)).unwrap();
+        let again = match line.stmt.alt
+        {
+            ast::StatementKind::Again(again) => again,
+            _ => panic!("Not an again")
+        };
+        let defer = match again.statement.alt
+        {
+            ast::StatementKind::Defer(defer) => defer,
+            _ => panic!("Not a defer")
+        };
+        match &defer.boolean.alt
+        {
+            ast::BooleanKind::BinOp(binop) =>
+                assert_eq!(binop.op.val, ast::BoolOp::Or),
+            _ => panic!("Not a binary boolean operation")
+        }
+        match defer.statement.alt
+        {
+            ast::StatementKind::Print(print) =>
+                assert_eq!(print.string.alt.get_str(), "N(1)"),
+            _ => panic!("Not a print")
+        }
+    }
+
+    #[test]
+    fn nesting_check()
+    {
+        let inputs = ["1 ((2));", "1 N(-3);", "1 defer (!(4)) 5;",
+                      "1 again (1) defer (2) 3;"];
+        for input in inputs.iter()
+        {
+            let cursor = lexer::Cursor::new(input).with_max_depth(2);
+            assert!(eat_line(cursor).is_ok());
+        }
+        let inputs = [("1 (((2)));", "2"), ("1 N(--3);", "3"),
+                      ("1 defer (!!(4)) 5;", "4"),
+                      ("1 again (1) defer (2) forget (3) 4;", "4")];
+        for &(input, found) in inputs.iter()
+        {
+            let cursor = lexer::Cursor::new(input).with_max_depth(2);
+            match eat_line(cursor)
+            {
+                Err(error) =>
+                {
+                    assert_eq!(error.kind, ErrorKind::NestingTooDeep);
+                    assert_eq!(error.found, found);
+                }
+                Ok(_) => panic!("Nesting is too deep in {}", input)
+            }
+        }
+
+        // Siblings are not nested
+        let input = "1 defer ((1) || (2) && !(3)) (4) + (5), 6#(7);";
+        let cursor = lexer::Cursor::new(input).with_max_depth(2);
+        let (_, cursor) = eat_line(cursor).unwrap();
+        assert_eq!(cursor.depth(), 0);
+    }
+
+    #[test]
+    fn long_list_check()
+    {
+        let input = format!("1 {};", vec!["2#3"; 5000].join(", "));
+        let (line, _) = eat_line(lexer::Cursor::new(&input)).unwrap();
+        let mut lineops = match &line.stmt.alt
+        {
+            ast::StatementKind::LineOperations(operations) =>
+                &operations.lineops,
+            _ => panic!("Not a list of line operations")
+        };
+        let mut len = 1;
+        while let ast::LineOpsKind::List(list) = &lineops.alt
+        {
+            assert_eq!(list.span.len(), input.len() - 3 - (len - 1) * 5);
+            lineops = &list.list;
+            len += 1;
         }
+        assert_eq!(len, 5000);
     }
 }
@@ -0,0 +1,243 @@
+use std::collections::BTreeSet;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::num::Int;
+
+/// Picks the next line to execute from the to-do list.
+pub trait Scheduler
+{
+    /// Returns the number of the next line to execute.
+    ///
+    /// `todo` holds the numbers of the lines in the to-do list, in increasing
+    /// order, each number once, along with the number of copies of the line
+    /// in the list, which is positive. It is never empty.
+    ///
+    /// Returns `None` if the scheduler has no line to offer.
+    fn pick(&mut self, todo: &[(usize, Int)]) -> Option<usize>;
+
+    /// Called after `line` was picked and executed. `changed` is `false` if
+    /// the to-do list is the same as before, for instance because the line
+    /// was deferred.
+    fn executed(&mut self, _line: usize, _changed: bool) {}
+}
+
+/// Picks lines at random, each copy of a line in the to-do list being as
+/// likely, with a xorshift64* pseudo-random number generator.
+///
+/// Numbers of copies that do not fit in a `u64` count as `u64::MAX` copies.
+pub struct Random
+{
+    state: u64
+}
+
+impl Random
+{
+    /// Returns a scheduler that picks the same lines for the same seed.
+    pub fn new(seed: u64) -> Random
+    {
+        // The state must not be 0, which it would stay forever
+        let state = match seed ^ 0x9e37_79b9_7f4a_7c15
+        {
+            0 => 0x2545_f491_4f6c_dd1d,
+            state => state
+        };
+        Random { state }
+    }
+
+    /// Returns a scheduler seeded from the current time.
+    pub fn from_time() -> Random
+    {
+        let seed = SystemTime::now().duration_since(UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_nanos() as u64);
+        Random::new(seed)
+    }
+
+    fn next(&mut self) -> u64
+    {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+}
+
+impl Scheduler for Random
+{
+    fn pick(&mut self, todo: &[(usize, Int)]) -> Option<usize>
+    {
+        let weights = todo.iter().map(|(_, count)| {
+            u128::from(count.to_u64().unwrap_or(u64::MAX))
+        });
+        let total: u128 = weights.clone().sum();
+        let draw = if total <= u128::from(u64::MAX)
+        {
+            u128::from(self.next())
+        }
+        else
+        {
+            u128::from(self.next()) << 64 | u128::from(self.next())
+        };
+        // The copy picked, counting from the first one of the first line
+        let mut index = draw % total;
+        for (&(line, _), weight) in todo.iter().zip(weights)
+        {
+            if index < weight
+            {
+                return Some(line);
+            }
+            index -= weight;
+        }
+        None
+    }
+}
+
+/// Picks the line with the lowest number.
+///
+/// Lines that left the to-do list unchanged are skipped until the to-do list
+/// changes, so that a deferred line does not keep the others from running.
+#[derive(Default)]
+pub struct LowestFirst
+{
+    idle: BTreeSet<usize>
+}
+
+impl LowestFirst
+{
+    pub fn new() -> LowestFirst
+    {
+        LowestFirst::default()
+    }
+}
+
+impl Scheduler for LowestFirst
+{
+    fn pick(&mut self, todo: &[(usize, Int)]) -> Option<usize>
+    {
+        match todo.iter().find(|(line, _)| !self.idle.contains(line))
+        {
+            Some(&(line, _)) => Some(line),
+            None =>
+            {
+                // No line changes anything, try them again
+                self.idle.clear();
+                Some(todo[0].0)
+            }
+        }
+    }
+
+    fn executed(&mut self, line: usize, changed: bool)
+    {
+        if changed
+        {
+            self.idle.clear();
+        }
+        else
+        {
+            self.idle.insert(line);
+        }
+    }
+}
+
+/// Picks the line following the last picked one, going back to the lowest
+/// line after the highest.
+#[derive(Default)]
+pub struct RoundRobin
+{
+    last: Option<usize>
+}
+
+impl RoundRobin
+{
+    pub fn new() -> RoundRobin
+    {
+        RoundRobin::default()
+    }
+}
+
+impl Scheduler for RoundRobin
+{
+    fn pick(&mut self, todo: &[(usize, Int)]) -> Option<usize>
+    {
+        let line = match self.last
+        {
+            Some(last) => todo.iter().map(|&(line, _)| line)
+                .find(|&line| line > last).unwrap_or(todo[0].0),
+            None => todo[0].0
+        };
+        self.last = Some(line);
+        Some(line)
+    }
+}
+
+/// Picks the lines of a given sequence, for instance one recorded from
+/// another run.
+pub struct Replay
+{
+    lines: std::vec::IntoIter<usize>
+}
+
+impl Replay
+{
+    pub fn new(lines: Vec<usize>) -> Replay
+    {
+        Replay { lines: lines.into_iter() }
+    }
+}
+
+impl Scheduler for Replay
+{
+    /// Returns the next line of the sequence, even if it is not in `todo`.
+    fn pick(&mut self, _todo: &[(usize, Int)]) -> Option<usize>
+    {
+        self.lines.next()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scheduler_check()
+    {
+        let todo = [(2, Int::from(1)), (5, Int::from(1)), (7, Int::from(1))];
+
+        let mut random = Random::new(42);
+        let picks: Vec<_> = (0..8).map(|_| random.pick(&todo).unwrap())
+            .collect();
+        let mut random = Random::new(42);
+        assert!(picks.iter().all(|line| random.pick(&todo) == Some(*line)));
+
+        // This seed would make the state 0, after which it would stay 0
+        let mut random = Random::new(0x9e37_79b9_7f4a_7c15);
+        let picks: BTreeSet<_> = (0..16).map(|_| random.pick(&todo).unwrap())
+            .collect();
+        assert!(picks.len() > 1);
+
+        // Lines are picked as often as they have copies
+        let weighted = [(1, Int::from(99)), (2, Int::from(1))];
+        let mut random = Random::new(42);
+        let ones = (0..1000).filter(|_| random.pick(&weighted) == Some(1))
+            .count();
+        assert!(ones > 950);
+
+        let mut lowest = LowestFirst::new();
+        assert_eq!(lowest.pick(&todo), Some(2));
+        lowest.executed(2, false);
+        assert_eq!(lowest.pick(&todo), Some(5));
+        lowest.executed(5, true);
+        assert_eq!(lowest.pick(&todo), Some(2));
+
+        let mut roundrobin = RoundRobin::new();
+        let picks: Vec<_> = (0..4).map(|_| roundrobin.pick(&todo).unwrap())
+            .collect();
+        assert_eq!(picks, [2, 5, 7, 2]);
+        assert_eq!(roundrobin.pick(&[(1, Int::from(1)), (3, Int::from(2))]),
+                   Some(3));
+
+        let mut replay = Replay::new(vec![7, 1]);
+        assert_eq!(replay.pick(&todo), Some(7));
+        assert_eq!(replay.pick(&todo), Some(1));
+        assert_eq!(replay.pick(&todo), None);
+    }
+}
@@ -0,0 +1,45 @@
+use std::ops::Range;
+
+/// A range of bytes in the input, from `start` included to `end` excluded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Span
+{
+    pub start: usize,
+    pub end: usize
+}
+
+impl Span
+{
+    pub fn new(start: usize, end: usize) -> Span
+    {
+        Span { start, end }
+    }
+
+    /// Returns the number of bytes spanned.
+    pub fn len(&self) -> usize
+    {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool
+    {
+        self.start == self.end
+    }
+
+    pub fn range(&self) -> Range<usize>
+    {
+        self.start..self.end
+    }
+}
+
+/// Returns the 1-based line and column of the byte `offset` in `src`.
+///
+/// Columns are counted in characters, not in bytes.
+pub fn line_column(src: &str, offset: usize) -> (usize, usize)
+{
+    let before = &src[..offset];
+    let line = before.matches('\n').count() + 1;
+    let linestart = before.rfind('\n').map_or(0, |pos| pos + 1);
+    let column = before[linestart..].chars().count() + 1;
+    (line, column)
+}
@@ -0,0 +1,415 @@
+// Traversal of the AST
+//
+// `Visitor` and `VisitorMut` have a method per node type. By default, methods
+// for nonterminals call the matching `walk_*` function, which visits the
+// children of the node in source order, and methods for terminals do nothing.
+// Implementors override the methods for the nodes they care about, and call
+// `walk_*` from them to keep going down the tree.
+//
+// Comments are not part of the grammar and are not visited.
+
+use crate::ast::*;
+
+// Dispatch from a node to the method of the visitor for its type
+trait Node<'a>
+{
+    fn accept<V: Visitor<'a> + ?Sized>(&self, visitor: &mut V);
+    fn accept_mut<V: VisitorMut<'a> + ?Sized>(&mut self, visitor: &mut V);
+}
+
+macro_rules! define_visitors
+{
+    (
+        terminals {
+            $($term: ident => $tvisit: ident, $tvisit_mut: ident;)*
+        }
+        nonterminals {
+            $($name: ident => $visit: ident, $visit_mut: ident,
+                              $walk: ident, $walk_mut: ident;)*
+        }
+    ) =>
+    {
+        /// A traversal of a borrowed AST.
+        pub trait Visitor<'a>
+        {
+            $(fn $tvisit(&mut self, _node: &$term<'a>) {})*
+            $(
+                fn $visit(&mut self, node: &$name<'a>)
+                {
+                    $walk(self, node)
+                }
+            )*
+        }
+
+        /// A traversal of a mutably borrowed AST.
+        pub trait VisitorMut<'a>
+        {
+            $(fn $tvisit_mut(&mut self, _node: &mut $term<'a>) {})*
+            $(
+                fn $visit_mut(&mut self, node: &mut $name<'a>)
+                {
+                    $walk_mut(self, node)
+                }
+            )*
+        }
+
+        $(
+            impl<'a> Node<'a> for $term<'a>
+            {
+                fn accept<V: Visitor<'a> + ?Sized>(&self, visitor: &mut V)
+                {
+                    visitor.$tvisit(self)
+                }
+                fn accept_mut<V: VisitorMut<'a> + ?Sized>(&mut self,
+                                                          visitor: &mut V)
+                {
+                    visitor.$tvisit_mut(self)
+                }
+            }
+        )*
+        $(
+            impl<'a> Node<'a> for $name<'a>
+            {
+                fn accept<V: Visitor<'a> + ?Sized>(&self, visitor: &mut V)
+                {
+                    visitor.$visit(self)
+                }
+                fn accept_mut<V: VisitorMut<'a> + ?Sized>(&mut self,
+                                                          visitor: &mut V)
+                {
+                    visitor.$visit_mut(self)
+                }
+            }
+        )*
+    }
+}
+
+define_visitors!
+{
+    terminals {
+        AgainToken => visit_again_token, visit_again_token_mut;
+        DeferToken => visit_defer_token, visit_defer_token_mut;
+        ForgetToken => visit_forget_token, visit_forget_token_mut;
+        NToken => visit_n_token, visit_n_token_mut;
+        PrintToken => visit_print_token, visit_print_token_mut;
+        ReadToken => visit_read_token, visit_read_token_mut;
+        UToken => visit_u_token, visit_u_token_mut;
+        PlusToken => visit_plus_token, visit_plus_token_mut;
+        MinusToken => visit_minus_token, visit_minus_token_mut;
+        UnBoolOpToken => visit_unboolop_token, visit_unboolop_token_mut;
+        BinBoolOpToken => visit_binboolop_token, visit_binboolop_token_mut;
+        BinNumBoolOpToken => visit_binnumboolop_token,
+                             visit_binnumboolop_token_mut;
+        MathOpToken => visit_mathop_token, visit_mathop_token_mut;
+        CommaToken => visit_comma_token, visit_comma_token_mut;
+        LeftParensToken => visit_leftparens_token,
+                           visit_leftparens_token_mut;
+        RightParensToken => visit_rightparens_token,
+                            visit_rightparens_token_mut;
+        SemicolonToken => visit_semicolon_token, visit_semicolon_token_mut;
+        SharpToken => visit_sharp_token, visit_sharp_token_mut;
+        NumberToken => visit_number_token, visit_number_token_mut;
+        StringToken => visit_string_token, visit_string_token_mut;
+        ErrorLine => visit_error_line, visit_error_line_mut;
+    }
+    nonterminals {
+        Program => visit_program, visit_program_mut,
+                   walk_program, walk_program_mut;
+        ProgramLine => visit_program_line, visit_program_line_mut,
+                       walk_program_line, walk_program_line_mut;
+        Line => visit_line, visit_line_mut, walk_line, walk_line_mut;
+
+        Number => visit_number, visit_number_mut,
+                  walk_number, walk_number_mut;
+        AbsNumber => visit_absnumber, visit_absnumber_mut,
+                     walk_absnumber, walk_absnumber_mut;
+        UnMathOp => visit_unmathop, visit_unmathop_mut,
+                    walk_unmathop, walk_unmathop_mut;
+        BinMathOp => visit_binmathop, visit_binmathop_mut,
+                     walk_binmathop, walk_binmathop_mut;
+        Boolean => visit_boolean, visit_boolean_mut,
+                   walk_boolean, walk_boolean_mut;
+        Statement => visit_statement, visit_statement_mut,
+                     walk_statement, walk_statement_mut;
+        LineOps => visit_lineops, visit_lineops_mut,
+                   walk_lineops, walk_lineops_mut;
+        SingleLineOp => visit_singlelineop, visit_singlelineop_mut,
+                        walk_singlelineop, walk_singlelineop_mut;
+        String_ => visit_string, visit_string_mut,
+                   walk_string, walk_string_mut;
+
+        AbsoluteNumber => visit_absolutenumber, visit_absolutenumber_mut,
+                          walk_absolutenumber, walk_absolutenumber_mut;
+        UnOpNumber => visit_unopnumber, visit_unopnumber_mut,
+                      walk_unopnumber, walk_unopnumber_mut;
+        BinOpNumber => visit_binopnumber, visit_binopnumber_mut,
+                       walk_binopnumber, walk_binopnumber_mut;
+        ParensNumber => visit_parensnumber, visit_parensnumber_mut,
+                        walk_parensnumber, walk_parensnumber_mut;
+        UnOpBoolean => visit_unopboolean, visit_unopboolean_mut,
+                       walk_unopboolean, walk_unopboolean_mut;
+        BinOpBoolean => visit_binopboolean, visit_binopboolean_mut,
+                        walk_binopboolean, walk_binopboolean_mut;
+        BinOpNumBoolean => visit_binopnumboolean, visit_binopnumboolean_mut,
+                           walk_binopnumboolean, walk_binopnumboolean_mut;
+        ParensBoolean => visit_parensboolean, visit_parensboolean_mut,
+                         walk_parensboolean, walk_parensboolean_mut;
+        N => visit_n, visit_n_mut, walk_n, walk_n_mut;
+        Read => visit_read, visit_read_mut, walk_read, walk_read_mut;
+        LineOperations => visit_lineoperations, visit_lineoperations_mut,
+                          walk_lineoperations, walk_lineoperations_mut;
+        LineOp => visit_lineop, visit_lineop_mut,
+                  walk_lineop, walk_lineop_mut;
+        CountLineOp => visit_countlineop, visit_countlineop_mut,
+                       walk_countlineop, walk_countlineop_mut;
+        LineOpList => visit_lineoplist, visit_lineoplist_mut,
+                      walk_lineoplist, walk_lineoplist_mut;
+        Again => visit_again, visit_again_mut, walk_again, walk_again_mut;
+        Defer => visit_defer, visit_defer_mut, walk_defer, walk_defer_mut;
+        Forget => visit_forget, visit_forget_mut,
+                  walk_forget, walk_forget_mut;
+        Print => visit_print, visit_print_mut, walk_print, walk_print_mut;
+        Concat => visit_concat, visit_concat_mut,
+                  walk_concat, walk_concat_mut;
+        U => visit_u, visit_u_mut, walk_u, walk_u_mut;
+
+        NumToBool => visit_numtobool, visit_numtobool_mut,
+                     walk_numtobool, walk_numtobool_mut;
+        NumToLineOp => visit_numtolineop, visit_numtolineop_mut,
+                       walk_numtolineop, walk_numtolineop_mut;
+        NumToString => visit_numtostring, visit_numtostring_mut,
+                       walk_numtostring, walk_numtostring_mut;
+        StringToNum => visit_stringtonum, visit_stringtonum_mut,
+                       walk_stringtonum, walk_stringtonum_mut;
+    }
+}
+
+// Walking down sequences: every field, in order
+macro_rules! define_walk
+{
+    ($name: ident, $walk: ident, $walk_mut: ident, $($field: ident),*) =>
+    {
+        pub fn $walk<'a, V>(visitor: &mut V, node: &$name<'a>)
+            where V: Visitor<'a> + ?Sized
+        {
+            $(node.$field.accept(visitor);)*
+        }
+        pub fn $walk_mut<'a, V>(visitor: &mut V, node: &mut $name<'a>)
+            where V: VisitorMut<'a> + ?Sized
+        {
+            $(node.$field.accept_mut(visitor);)*
+        }
+    }
+}
+
+// Walking down alternations: the node in the enum
+macro_rules! define_walk_alternation
+{
+    ($name: ident, $kind: ident, $walk: ident, $walk_mut: ident,
+     $($variant: ident),*) =>
+    {
+        pub fn $walk<'a, V>(visitor: &mut V, node: &$name<'a>)
+            where V: Visitor<'a> + ?Sized
+        {
+            match &node.alt
+            {
+                $($kind::$variant(node) => node.accept(visitor),)*
+            }
+        }
+        pub fn $walk_mut<'a, V>(visitor: &mut V, node: &mut $name<'a>)
+            where V: VisitorMut<'a> + ?Sized
+        {
+            match &mut node.alt
+            {
+                $($kind::$variant(node) => node.accept_mut(visitor),)*
+            }
+        }
+    }
+}
+
+pub fn walk_program<'a, V>(visitor: &mut V, node: &Program<'a>)
+    where V: Visitor<'a> + ?Sized
+{
+    for line in &node.lines
+    {
+        line.accept(visitor);
+    }
+}
+pub fn walk_program_mut<'a, V>(visitor: &mut V, node: &mut Program<'a>)
+    where V: VisitorMut<'a> + ?Sized
+{
+    for line in &mut node.lines
+    {
+        line.accept_mut(visitor);
+    }
+}
+
+pub fn walk_program_line<'a, V>(visitor: &mut V, node: &ProgramLine<'a>)
+    where V: Visitor<'a> + ?Sized
+{
+    match node
+    {
+        ProgramLine::Line(line) => line.accept(visitor),
+        ProgramLine::Error(error) => error.accept(visitor)
+    }
+}
+pub fn walk_program_line_mut<'a, V>(visitor: &mut V,
+                                    node: &mut ProgramLine<'a>)
+    where V: VisitorMut<'a> + ?Sized
+{
+    match node
+    {
+        ProgramLine::Line(line) => line.accept_mut(visitor),
+        ProgramLine::Error(error) => error.accept_mut(visitor)
+    }
+}
+
+define_walk!(Line, walk_line, walk_line_mut, num, stmt, semi);
+
+define_walk_alternation!(Number, NumberKind, walk_number, walk_number_mut,
+                         Absolute, UnOp, BinOp, Parens, StringToNum);
+define_walk_alternation!(AbsNumber, AbsNumberKind,
+                         walk_absnumber, walk_absnumber_mut,
+                         Number, N, Read);
+define_walk_alternation!(UnMathOp, UnMathOpKind,
+                         walk_unmathop, walk_unmathop_mut,
+                         Plus, Minus);
+define_walk_alternation!(BinMathOp, BinMathOpKind,
+                         walk_binmathop, walk_binmathop_mut,
+                         Plus, Minus, MathOp);
+define_walk_alternation!(Boolean, BooleanKind, walk_boolean, walk_boolean_mut,
+                         UnOp, BinOp, BinOpNum, Parens, NumToBool);
+define_walk_alternation!(Statement, StatementKind,
+                         walk_statement, walk_statement_mut,
+                         LineOperations, Again, Defer, Forget, Print);
+define_walk_alternation!(LineOps, LineOpsKind, walk_lineops, walk_lineops_mut,
+                         LineOp, List);
+define_walk_alternation!(SingleLineOp, SingleLineOpKind,
+                         walk_singlelineop, walk_singlelineop_mut,
+                         NumToLineOp, Count);
+define_walk_alternation!(String_, StringKind, walk_string, walk_string_mut,
+                         String, U, Concat, NumToString);
+
+define_walk!(AbsoluteNumber, walk_absolutenumber, walk_absolutenumber_mut,
+             num);
+define_walk!(UnOpNumber, walk_unopnumber, walk_unopnumber_mut, op, num);
+define_walk!(BinOpNumber, walk_binopnumber, walk_binopnumber_mut,
+             num1, op, num2);
+define_walk!(ParensNumber, walk_parensnumber, walk_parensnumber_mut,
+             lparen, num, rparen);
+define_walk!(UnOpBoolean, walk_unopboolean, walk_unopboolean_mut,
+             op, boolean);
+define_walk!(BinOpBoolean, walk_binopboolean, walk_binopboolean_mut,
+             boolean1, op, boolean2);
+define_walk!(BinOpNumBoolean, walk_binopnumboolean, walk_binopnumboolean_mut,
+             num1, op, num2);
+define_walk!(ParensBoolean, walk_parensboolean, walk_parensboolean_mut,
+             lparen, boolean, rparen);
+define_walk!(N, walk_n, walk_n_mut, keyword, lparen, num, rparen);
+define_walk!(Read, walk_read, walk_read_mut, keyword, lparen, rparen);
+define_walk!(LineOperations, walk_lineoperations, walk_lineoperations_mut,
+             lineops);
+define_walk!(LineOp, walk_lineop, walk_lineop_mut, slo);
+define_walk!(CountLineOp, walk_countlineop, walk_countlineop_mut,
+             line, sharp, count);
+define_walk!(LineOpList, walk_lineoplist, walk_lineoplist_mut,
+             lineop, comma, list);
+define_walk!(Again, walk_again, walk_again_mut,
+             keyword, lparen, boolean, rparen, statement);
+define_walk!(Defer, walk_defer, walk_defer_mut,
+             keyword, lparen, boolean, rparen, statement);
+define_walk!(Forget, walk_forget, walk_forget_mut,
+             keyword, lparen, boolean, rparen, statement);
+define_walk!(Print, walk_print, walk_print_mut,
+             keyword, lparen, string, rparen);
+define_walk!(Concat, walk_concat, walk_concat_mut, str1, op, str2);
+define_walk!(U, walk_u, walk_u_mut, keyword, lparen, num, rparen);
+
+define_walk!(NumToBool, walk_numtobool, walk_numtobool_mut, num);
+define_walk!(NumToLineOp, walk_numtolineop, walk_numtolineop_mut, num);
+define_walk!(NumToString, walk_numtostring, walk_numtostring_mut, num);
+define_walk!(StringToNum, walk_stringtonum, walk_stringtonum_mut, string);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::lexer;
+    use crate::num::{Int, OverflowMode};
+    use crate::parser::eat_program;
+
+    // Collects the tokens with a value, and the strings printed
+    #[derive(Default)]
+    struct Collector<'a>
+    {
+        tokens: Vec<&'a str>,
+        prints: Vec<&'a str>,
+        vals: Vec<Int>
+    }
+
+    impl<'a> Visitor<'a> for Collector<'a>
+    {
+        fn visit_print(&mut self, node: &Print<'a>)
+        {
+            self.prints.push(node.string.alt.get_str());
+            walk_print(self, node)
+        }
+        fn visit_number_token(&mut self, node: &NumberToken<'a>)
+        {
+            self.tokens.push(node.tok);
+            self.vals.push(node.val.clone());
+        }
+        fn visit_binnumboolop_token(&mut self, node: &BinNumBoolOpToken<'a>)
+        {
+            self.tokens.push(node.tok);
+        }
+        fn visit_string_token(&mut self, node: &StringToken<'a>)
+        {
+            self.tokens.push(node.tok);
+        }
+    }
+
+    // Doubles every number, except line numbers
+    struct Doubler;
+
+    impl<'a> VisitorMut<'a> for Doubler
+    {
+        fn visit_line_mut(&mut self, node: &mut Line<'a>)
+        {
+            self.visit_statement_mut(&mut node.stmt);
+        }
+        fn visit_number_token_mut(&mut self, node: &mut NumberToken<'a>)
+        {
+            node.val = OverflowMode::Wrapping.add(&node.val, &node.val)
+                .unwrap();
+        }
+    }
+
+    #[test]
+    fn visitor_check()
+    {
+        let input = "1 defer (N(2) <= 3) print(\"a\" + U(0x41));\n2 4#2,3;";
+        let (program, _) = eat_program(lexer::Cursor::new(input)).unwrap();
+
+        let mut collector = Collector::default();
+        collector.visit_program(&program);
+        assert_eq!(collector.tokens, vec!["1", "2", "<=", "3", "\"a\"", "0x41",
+                                          "2", "4", "2", "3"]);
+        assert_eq!(collector.prints, vec!["\"a\" + U(0x41)"]);
+    }
+
+    #[test]
+    fn visitor_mut_check()
+    {
+        let input = "1 1#2,3;";
+        let (mut program, _) = eat_program(lexer::Cursor::new(input)).unwrap();
+
+        Doubler.visit_program_mut(&mut program);
+
+        let mut collector = Collector::default();
+        collector.visit_program(&program);
+        assert_eq!(collector.tokens, vec!["1", "1", "2", "3"]);
+        assert_eq!(collector.vals, vec![Int::from(1), Int::from(2),
+                                        Int::from(4), Int::from(6)]);
+    }
+}
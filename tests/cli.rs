@@ -0,0 +1,110 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// Runs the binary with `args`, giving it `stdin`.
+///
+/// Returns its exit code, output and error output.
+fn whenever(args: &[&str], stdin: &str) -> (i32, String, String)
+{
+    let mut child = Command::new(env!("CARGO_BIN_EXE_whenever"))
+        .args(args)
+        .env("NO_COLOR", "1")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(stdin.as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+    (output.status.code().unwrap(),
+     String::from_utf8(output.stdout).unwrap(),
+     String::from_utf8(output.stderr).unwrap())
+}
+
+#[test]
+fn cli_check()
+{
+    assert_eq!(whenever(&["check", "tests/fibo.wnvr"], ""),
+               (0, String::new(), String::new()));
+
+    let (code, _, stderr) = whenever(&["check", "-"],
+                                     "1 2;\n2 Again (1) 3;\n3 (;\n");
+    assert_eq!(code, 1);
+    assert!(stderr.contains("error: unknown keyword `Again`\n \
+                             --> <stdin>:2:3\n"));
+    assert!(stderr.contains("error: expected number, found `;`\n \
+                             --> <stdin>:3:4\n"));
+
+    let (code, _, stderr) = whenever(&["check", "-"], "1 2;\n1 3;\n");
+    assert_eq!(code, 1);
+    assert!(stderr.starts_with("error: line 1 is defined twice\n"));
+
+    let (code, _, stderr) = whenever(&["check", "--max-depth", "1", "-"],
+                                     "1 ((2));\n");
+    assert_eq!(code, 1);
+    assert!(stderr.starts_with("error: nesting too deep at `2`\n"));
+}
+
+#[test]
+fn cli_dot()
+{
+    let (code, stdout, _) = whenever(&["dot", "-"], "1 1;\n");
+    assert_eq!(code, 0);
+    assert!(stdout.starts_with("digraph {\n  \"0x0_5_Program\""));
+    assert!(stdout.ends_with("  \"0x3_1\" [label=\";\"];\n}\n"));
+}
+
+#[test]
+fn cli_fmt()
+{
+    let (code, stdout, _) = whenever(&["fmt", "--compact", "--sort", "-"],
+                                     "2 defer (N(1) > 0) 1;\n1 2 , 3#4;\n");
+    assert_eq!(code, 0);
+    assert_eq!(stdout, "1 2, 3#4;\n2 defer (N(1)>0) 1;\n");
+
+    let (code, _, stderr) = whenever(&["fmt", "--check", "tests/fibo.wnvr"],
+                                     "");
+    assert_eq!(code, 1);
+    assert_eq!(stderr, "whenever: tests/fibo.wnvr is not formatted\n");
+    let (code, _, _) = whenever(&["fmt", "--check", "tests/hello.wnvr"], "");
+    assert_eq!(code, 0);
+}
+
+#[test]
+fn cli_run()
+{
+    let (code, stdout, _) = whenever(&["run", "--seed", "0",
+                                       "tests/hello.wnvr"], "");
+    assert_eq!(code, 0);
+    assert_eq!(stdout, "Hello world!\nHello world!\n");
+
+    // The program comes from the standard input, its input from a file
+    // starting with `4`
+    let (code, stdout, _) = whenever(&["run", "--input", "tests/hello.wnvr",
+                                       "-"], "1 print(read());\n");
+    assert_eq!(code, 0);
+    assert_eq!(stdout, "52\n");
+
+    let (code, stdout, stderr) = whenever(&["run", "--steps", "2", "-"],
+                                          "1 again (1) print(\"a\");\n");
+    assert_eq!(code, 1);
+    assert_eq!(stdout, "a\na\n");
+    assert_eq!(stderr, "whenever: stopped after 2 steps, with lines left to \
+                        execute\n");
+}
+
+#[test]
+fn cli_usage()
+{
+    let (code, stdout, _) = whenever(&["--help"], "");
+    assert_eq!(code, 0);
+    assert!(stdout.starts_with("Usage: whenever <command>"));
+
+    for args in [&["frobnicate", "-"][..], &["run"], &["fmt", "--seed", "1"],
+                 &["run", "--steps", "many", "-"], &["dot", "a", "b"]].iter()
+    {
+        let (code, _, stderr) = whenever(args, "");
+        assert_eq!(code, 2, "{:?}", args);
+        assert!(stderr.starts_with("whenever: "));
+    }
+}
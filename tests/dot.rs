@@ -1,6 +1,4 @@
-use whenever_parser;
-
-use whenever_parser::ast;
+use whenever_parser::ast::Graph;
 
 #[test]
 fn to_dot_1_1()
@@ -24,7 +22,7 @@ fn to_dot_1_1()
 "#;
 
     let line = whenever_parser::parse_line(input).unwrap();
-    let actual = ast::to_dot_normalized(&line);
+    let actual = line.to_dot();
     assert_eq!(actual, expected);
 }
 
@@ -36,6 +34,187 @@ fn to_dot_fibo()
     let expected = include_str!("fibo1.dot");
 
     let line = whenever_parser::parse_line(input).unwrap();
-    let actual = format!("digraph {{\n{}}}\n", ast::to_dot_normalized(&line));
+    let actual = format!("digraph {{\n{}}}\n", line.to_dot());
     assert_eq!(actual, expected);
 }
+
+#[test]
+fn to_dot_program()
+{
+    let input = "1 1;\n2 1;\n";
+    let expected = r#"  "0x0_10_Program" [label=<<I>Program</I>>];
+  "0x0_10_Program" -> "0x0_4_Line";
+  "0x0_4_Line" [label=<<I>Line</I>>];
+  "0x0_4_Line" -> "0x0_1";
+  "0x0_1" [label="1 (1)"];
+  "0x0_4_Line" -> "0x2_1_LineOperations";
+  "0x2_1_LineOperations" [label=<<I>LineOperations</I>>];
+  "0x2_1_LineOperations" -> "0x2_1_LineOp";
+  "0x2_1_LineOp" [label=<<I>LineOp</I>>];
+  "0x2_1_LineOp" -> "0x2_1_AbsoluteNumber_NumToLineOp";
+  "0x2_1_AbsoluteNumber_NumToLineOp" [label=<<I>Number ⮕ SingleLineOp</I>>];
+  "0x2_1_AbsoluteNumber_NumToLineOp" -> "0x2_1_AbsoluteNumber";
+  "0x2_1_AbsoluteNumber" [label=<<I>AbsoluteNumber</I>>];
+  "0x2_1_AbsoluteNumber" -> "0x2_1";
+  "0x2_1" [label="1 (1)"];
+  "0x0_4_Line" -> "0x3_1";
+  "0x3_1" [label=";"];
+  "0x0_10_Program" -> "0x5_4_Line";
+  "0x5_4_Line" [label=<<I>Line</I>>];
+  "0x5_4_Line" -> "0x5_1";
+  "0x5_1" [label="2 (2)"];
+  "0x5_4_Line" -> "0x7_1_LineOperations";
+  "0x7_1_LineOperations" [label=<<I>LineOperations</I>>];
+  "0x7_1_LineOperations" -> "0x7_1_LineOp";
+  "0x7_1_LineOp" [label=<<I>LineOp</I>>];
+  "0x7_1_LineOp" -> "0x7_1_AbsoluteNumber_NumToLineOp";
+  "0x7_1_AbsoluteNumber_NumToLineOp" [label=<<I>Number ⮕ SingleLineOp</I>>];
+  "0x7_1_AbsoluteNumber_NumToLineOp" -> "0x7_1_AbsoluteNumber";
+  "0x7_1_AbsoluteNumber" [label=<<I>AbsoluteNumber</I>>];
+  "0x7_1_AbsoluteNumber" -> "0x7_1";
+  "0x7_1" [label="1 (1)"];
+  "0x5_4_Line" -> "0x8_1";
+  "0x8_1" [label=";"];
+"#;
+
+    let program = whenever_parser::parse_program(input).unwrap();
+    let actual = program.to_dot();
+    assert_eq!(actual, expected);
+}
+
+// Checks that every edge of `edges` is in the dot output for `input`
+macro_rules! check_edges
+{
+    ($input: expr, $($edge: expr),*) =>
+    {
+        {
+            let line = whenever_parser::parse_line($input).unwrap();
+            let dot = line.to_dot();
+            $(
+                let edge = format!("  {};\n", $edge);
+                assert!(dot.contains(&edge), "No {} in:\n{}", $edge, dot);
+            )*
+        }
+    };
+}
+
+#[test]
+fn to_dot_left_associativity()
+{
+    // (1-2)+3
+    check_edges!("1 1-2+3;",
+                 r#""0x2_5_BinOpNumber" -> "0x2_3_BinOpNumber""#,
+                 r#""0x2_5_BinOpNumber" -> "0x5_1""#,
+                 r#""0x2_5_BinOpNumber" -> "0x6_1_AbsoluteNumber""#,
+                 r#""0x2_3_BinOpNumber" -> "0x3_1""#);
+    // (N(1)-N(2))+3
+    check_edges!("1 N(1)-N(2)+3;",
+                 r#""0x2_11_BinOpNumber" -> "0x2_9_BinOpNumber""#,
+                 r#""0x2_11_BinOpNumber" -> "0xc_1_AbsoluteNumber""#);
+    // (8/4)*2
+    check_edges!("1 8/4*2;",
+                 r#""0x2_5_BinOpNumber" -> "0x2_3_BinOpNumber""#,
+                 r#""0x2_5_BinOpNumber" -> "0x6_1_AbsoluteNumber""#);
+}
+
+#[test]
+fn to_dot_precedence()
+{
+    // 1+(2*3)
+    check_edges!("1 1+2*3;",
+                 r#""0x2_5_BinOpNumber" -> "0x2_1_AbsoluteNumber""#,
+                 r#""0x2_5_BinOpNumber" -> "0x4_3_BinOpNumber""#);
+    // (2*3)+1
+    check_edges!("1 2*3+1;",
+                 r#""0x2_5_BinOpNumber" -> "0x2_3_BinOpNumber""#,
+                 r#""0x2_5_BinOpNumber" -> "0x6_1_AbsoluteNumber""#);
+    // ((-1)*2)-(3/(+4))
+    check_edges!("1 -1*2-3/+4;",
+                 r#""0x2_9_BinOpNumber" -> "0x2_4_BinOpNumber""#,
+                 r#""0x2_4_BinOpNumber" -> "0x2_2_UnOpNumber""#,
+                 r#""0x2_9_BinOpNumber" -> "0x7_4_BinOpNumber""#,
+                 r#""0x7_4_BinOpNumber" -> "0x9_2_UnOpNumber""#);
+    // (1+2)*3
+    check_edges!("1 (1+2)*3;",
+                 r#""0x2_7_BinOpNumber" -> "0x2_5_ParensNumber""#,
+                 r#""0x3_3_BinOpNumber" -> "0x3_1_AbsoluteNumber""#);
+}
+
+#[test]
+fn to_dot_concat_associativity()
+{
+    // ("a"+1)+2
+    check_edges!(r#"1 print("a"+1+2);"#,
+                 r#""0x8_7_Concat" -> "0x8_5_Concat""#,
+                 r#""0x8_7_Concat" -> "0xe_1_AbsoluteNumber_NumToString""#);
+    // "a"+(1*2)
+    check_edges!(r#"1 print("a"+1*2);"#,
+                 r#""0x8_7_Concat" -> "0xc_3_BinOpNumber_NumToString""#);
+    // "a"+(1-2)
+    check_edges!(r#"1 print("a"+1-2);"#,
+                 r#""0x8_7_Concat" -> "0xc_3_BinOpNumber_NumToString""#);
+    // "n="+(N(1)-1)
+    check_edges!(r#"1 print("n="+N(1)-1);"#,
+                 r#""0x8_11_Concat" -> "0xd_6_BinOpNumber_NumToString""#);
+    // ("a"+(1-2))+3
+    check_edges!(r#"1 print("a"+1-2+3);"#,
+                 r#""0x8_9_Concat" -> "0x8_7_Concat""#,
+                 r#""0x8_7_Concat" -> "0xc_3_BinOpNumber_NumToString""#,
+                 r#""0x8_9_Concat" -> "0x10_1_AbsoluteNumber_NumToString""#);
+}
+
+#[test]
+fn to_dot_boolean_precedence()
+{
+    // 1||(2&&3)
+    check_edges!("1 defer (1||2&&3) 4;",
+                 r#""0x9_7_BinOpBoolean" -> "0x9_1_AbsoluteNumber_NumToBool""#,
+                 r#""0x9_7_BinOpBoolean" -> "0xc_4_BinOpBoolean""#);
+    // (1&&2)||3
+    check_edges!("1 defer (1&&2||3) 4;",
+                 r#""0x9_7_BinOpBoolean" -> "0x9_4_BinOpBoolean""#,
+                 r#""0x9_7_BinOpBoolean" -> "0xf_1_AbsoluteNumber_NumToBool""#);
+    // ((1||2)||3)
+    check_edges!("1 defer (1||2||3) 4;",
+                 r#""0x9_7_BinOpBoolean" -> "0x9_4_BinOpBoolean""#);
+    // (!1)&&(N(2)!=3)
+    check_edges!("1 defer (!1&&N(2)!=3) 4;",
+                 r#""0x9_11_BinOpBoolean" -> "0x9_2_UnOpBoolean""#,
+                 r#""0x9_11_BinOpBoolean" -> "0xd_7_BinOpNumBoolean""#);
+    // (!(1))||(!(!2))
+    check_edges!("1 defer (!(1)||!!2) 4;",
+                 r#""0x9_4_UnOpBoolean" -> "0xa_3_ParensBoolean""#,
+                 r#""0xf_3_UnOpBoolean" -> "0x10_2_UnOpBoolean""#);
+}
+
+#[test]
+fn to_dot_parenthesized_comparison()
+{
+    // ((N(1)+1)>2)||(3)
+    check_edges!("1 defer ((N(1)+1)>2||(3)) 4;",
+                 r#""0x9_15_BinOpBoolean" -> "0x9_10_BinOpNumBoolean""#,
+                 r#""0x9_10_BinOpNumBoolean" -> "0x9_8_ParensNumber""#,
+                 r#""0x9_15_BinOpBoolean" -> "0x15_3_ParensBoolean""#);
+}
+
+#[test]
+fn negation_binds_tighter_than_comparison()
+{
+    // (!1)==2 does not make sense, as booleans cannot be compared
+    assert!(whenever_parser::parse_line("1 defer (!1==2) 3;").is_err());
+    assert!(whenever_parser::parse_line("1 defer (!(1==2)) 3;").is_ok());
+}
+
+#[test]
+fn to_dot_hexadecimal_labels()
+{
+    // Identifiers do not depend on where the input is, nor on its text
+    check_edges!("1 0x1f;",
+                 r#""0x2_4" [label="0x1f (31)"]"#,
+                 r#""0x2_4_AbsoluteNumber" -> "0x2_4""#);
+    let dot1 = whenever_parser::parse_line("1 print(\"0x0\");").unwrap()
+        .to_dot();
+    let dot2 = whenever_parser::parse_line(&String::from("1 print(\"0x0\");"))
+        .unwrap().to_dot();
+    assert_eq!(dot1, dot2);
+}
@@ -0,0 +1,88 @@
+use whenever_parser::format::{self, Style};
+use whenever_parser::interp::Interpreter;
+use whenever_parser::io::Memory;
+use whenever_parser::scheduler;
+
+fn format(program: &str, style: &Style) -> String
+{
+    let program = whenever_parser::parse_program(program).unwrap();
+    format::format_program(&program, style)
+}
+
+fn run(program: &str, seed: u64) -> String
+{
+    let program = whenever_parser::parse_program(program).unwrap();
+    let scheduler = Box::new(scheduler::Random::new(seed));
+    let mut io = Memory::new("");
+    Interpreter::with_scheduler(&program, scheduler).unwrap()
+        .run(&mut io).unwrap();
+    io.output
+}
+
+#[test]
+fn format_fibo()
+{
+    let expected = "\
+1 again (1) defer (3 || N(1) <= N(2) || N(7) > 99) 2#N(1), 3, 7;
+2 again (2) defer (3 || N(2) <= N(1) || N(7) > 99) 1#N(2), 3, 7;
+3 defer (5) print(N(1) + N(2));
+4 defer (5) print(\"1\");
+5 4, -3, 7;
+6 defer (4) 3;
+7 7;
+8 defer (N(7) < 100) -1#N(1), -2#N(2), -7#100, -3;
+9 defer (3 || 6) 1, 3;
+";
+    assert_eq!(format(include_str!("fibo.wnvr"), &Style::default()),
+               expected);
+}
+
+#[test]
+fn format_beer()
+{
+    // Print statements are never broken, even when too long
+    let expected = concat!(
+        "1 defer (4 || N(1) < N(2) || N(2) < N(3))\n",
+        "    print(N(1) + \" bottles of beer on the wall, \" + N(1) + ",
+        "\" bottles of beer,\");\n",
+        "2 defer (4 || N(1) == N(2)) ",
+        "print(\"Take one down and pass it around,\");\n",
+        "3 defer (4 || N(2) == N(3)) ",
+        "print(N(1) + \" bottles of beer on the wall.\");\n",
+        "4 1#98, 2#98, 3#98;\n");
+    let formatted = format(include_str!("beer.wnvr"), &Style::default());
+    assert_eq!(formatted, expected);
+    // Formatting does not change what the program does
+    assert_eq!(run(&formatted, 0), run(include_str!("beer.wnvr"), 0));
+}
+
+#[test]
+fn format_idempotent()
+{
+    let styles = [
+        Style::default(),
+        Style { width: 30, indent: 2, ..Style::default() },
+        Style { spaced_operators: false, sort_lines: true, ..Style::default() }
+    ];
+    let programs = [
+        include_str!("fibo.wnvr"),
+        include_str!("beer.wnvr"),
+        include_str!("hello.wnvr")
+    ];
+    for style in &styles
+    {
+        for program in &programs
+        {
+            let formatted = format(program, style);
+            assert_eq!(format(&formatted, style), formatted);
+        }
+    }
+}
+
+#[test]
+fn format_sorted()
+{
+    let style = Style { sort_lines: true, ..Style::default() };
+    assert_eq!(format(include_str!("hello.wnvr"), &style),
+               "10 42;\n42 print(\"Hello world!\");\n");
+}
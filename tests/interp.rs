@@ -0,0 +1,217 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use whenever_parser::interp::{Interpreter, RuntimeError, RuntimeErrorKind};
+use whenever_parser::io::Memory;
+use whenever_parser::num::Int;
+use whenever_parser::scheduler::{self, Scheduler};
+
+/// Returns the output of `program`, and the error that stopped it, if any.
+fn run_with(program: &str, scheduler: Box<dyn Scheduler>)
+    -> (String, Option<RuntimeError>)
+{
+    let program = whenever_parser::parse_program(program).unwrap();
+    let mut io = Memory::new("");
+    let res = Interpreter::with_scheduler(&program, scheduler).unwrap()
+        .run(&mut io);
+    (io.output, res.err())
+}
+
+fn run(program: &str, seed: u64) -> String
+{
+    let (output, error) =
+        run_with(program, Box::new(scheduler::Random::new(seed)));
+    assert_eq!(error, None);
+    output
+}
+
+#[test]
+fn run_hello()
+{
+    for seed in 0..10
+    {
+        assert_eq!(run(include_str!("hello.wnvr"), seed),
+                   "Hello world!\nHello world!\n");
+    }
+}
+
+#[test]
+fn run_beer()
+{
+    let mut expected = String::new();
+    for i in (1..=99).rev()
+    {
+        expected += &format!("{} bottles of beer on the wall, {} bottles of \
+                              beer,\n\
+                              Take one down and pass it around,\n\
+                              {} bottles of beer on the wall.\n",
+                             i, i, i - 1);
+    }
+    for seed in 0..3
+    {
+        assert_eq!(run(include_str!("beer.wnvr"), seed), expected);
+    }
+}
+
+#[test]
+fn run_fibo()
+{
+    let mut fibo = vec![1u128, 1];
+    while fibo.len() < 102
+    {
+        fibo.push(fibo[fibo.len() - 2] + fibo[fibo.len() - 1]);
+    }
+    // Lines 1 and 2 end up with as many copies as the numbers printed, so a
+    // random scheduler would nearly always pick them, only to defer them
+    let schedulers: [Box<dyn Scheduler>; 2] =
+        [Box::new(scheduler::LowestFirst::new()),
+         Box::new(scheduler::RoundRobin::new())];
+    for scheduler in schedulers
+    {
+        let (output, error) = run_with(include_str!("fibo.wnvr"), scheduler);
+        let numbers: Vec<u128> =
+            output.lines().map(|line| line.parse().unwrap()).collect();
+        assert_eq!(numbers, fibo[..numbers.len()]);
+
+        if cfg!(feature = "bigint")
+        {
+            // Whether the last number is printed depends on which of lines 3
+            // and 8 runs first
+            assert!(numbers.len() == 101 || numbers.len() == 102);
+            assert_eq!(error, None);
+        }
+        else
+        {
+            // The 93rd number does not fit in 64 bits
+            assert_eq!(numbers.len(), 92);
+            assert_eq!(error.unwrap().kind, RuntimeErrorKind::Overflow);
+        }
+    }
+}
+
+#[test]
+fn run_any_scheduler()
+{
+    for program in &[include_str!("hello.wnvr"), include_str!("beer.wnvr")]
+    {
+        let expected = run(program, 0);
+        let lowest = Box::new(scheduler::LowestFirst::new());
+        assert_eq!(run_with(program, lowest), (expected.clone(), None));
+        let roundrobin = Box::new(scheduler::RoundRobin::new());
+        assert_eq!(run_with(program, roundrobin), (expected, None));
+    }
+
+    let program = include_str!("fibo.wnvr");
+    let lowest = Box::new(scheduler::LowestFirst::new());
+    let expected = run_with(program, lowest).0;
+    let roundrobin = Box::new(scheduler::RoundRobin::new());
+    assert_eq!(run_with(program, roundrobin).0, expected);
+}
+
+#[test]
+fn run_replay()
+{
+    let program = whenever_parser::parse_program(include_str!("beer.wnvr"))
+        .unwrap();
+
+    // Record a run, then replay it
+    let mut interpreter = Interpreter::with_seed(&program, 7).unwrap();
+    let mut expected = Memory::new("");
+    let mut lines = Vec::new();
+    while let Some(line) = interpreter.step(&mut expected).unwrap()
+    {
+        lines.push(line);
+    }
+
+    let replay = Box::new(scheduler::Replay::new(lines));
+    let mut interpreter = Interpreter::with_scheduler(&program, replay)
+        .unwrap();
+    let mut io = Memory::new("");
+    interpreter.run(&mut io).unwrap();
+    assert_eq!(io.output, expected.output);
+
+    // Lines 1 to 3 are deferred until line 4 runs, so they stay in the to-do
+    // list
+    let replay = Box::new(scheduler::Replay::new(vec![1, 2, 4, 4]));
+    let mut interpreter = Interpreter::with_scheduler(&program, replay)
+        .unwrap();
+    let error = interpreter.run(&mut Memory::new("")).err().unwrap();
+    assert_eq!(error.kind, RuntimeErrorKind::Schedule);
+    assert_eq!(error.to_string(),
+               "line 4 was scheduled but is not in the to-do list");
+}
+
+#[test]
+fn run_changed()
+{
+    // What the interpreter told the scheduler
+    #[derive(Default)]
+    struct Log
+    {
+        todos: Vec<Vec<usize>>,
+        changes: Vec<bool>
+    }
+
+    // Replays `picks`, in reverse order, and fills `log`
+    struct Record
+    {
+        picks: Vec<usize>,
+        log: Rc<RefCell<Log>>
+    }
+
+    impl Scheduler for Record
+    {
+        fn pick(&mut self, todo: &[(usize, Int)]) -> Option<usize>
+        {
+            let lines = todo.iter().map(|&(line, _)| line).collect();
+            self.log.borrow_mut().todos.push(lines);
+            self.picks.pop()
+        }
+
+        fn executed(&mut self, _line: usize, changed: bool)
+        {
+            self.log.borrow_mut().changes.push(changed);
+        }
+    }
+
+    let program = whenever_parser::parse_program(
+        "1 1;\n2 3;\n3 defer (N(2)>0) print(\"x\");\n").unwrap();
+    let log = Rc::new(RefCell::new(Log::default()));
+    let record = Box::new(Record { picks: vec![3, 3, 2, 3, 1],
+                                   log: Rc::clone(&log) });
+    let mut interpreter = Interpreter::with_scheduler(&program, record)
+        .unwrap();
+    let mut io = Memory::new("");
+    for _ in 0..5
+    {
+        interpreter.step(&mut io).unwrap();
+    }
+    assert_eq!(io.output, "x\nx\n");
+
+    // Line 1 puts itself back and line 3 is deferred, so the to-do list only
+    // changes from the third step on
+    let log = log.borrow();
+    assert_eq!(log.todos, vec![vec![1, 2, 3], vec![1, 2, 3], vec![1, 2, 3],
+                               vec![1, 3], vec![1, 3]]);
+    assert_eq!(log.changes, vec![false, false, true, true, true]);
+}
+
+#[test]
+fn run_weighted()
+{
+    // Line 1 adds 98 copies of itself at each step, so once it ran, line 2
+    // is rarely picked
+    let program = whenever_parser::parse_program(
+        "1 1#99;\n2 print(\"x\");\n").unwrap();
+    let printed = (0..20).filter(|&seed| {
+        let mut interpreter = Interpreter::with_seed(&program, seed).unwrap();
+        let mut io = Memory::new("");
+        for _ in 0..10
+        {
+            interpreter.step(&mut io).unwrap();
+        }
+        io.output == "x\n"
+    }).count();
+    // Picking lines regardless of their copies would print in nearly every run
+    assert!(printed < 15);
+}
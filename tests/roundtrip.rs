@@ -0,0 +1,442 @@
+use whenever_parser::ast::*;
+use whenever_parser::format::{self, Style};
+use whenever_parser::visit::*;
+
+// Where a number is read, which restricts what it may start with
+#[derive(Clone, Copy, PartialEq)]
+enum Lead
+{
+    Any,
+    // Operand of a boolean: `(` would start a boolean
+    NoParens,
+    // First operand of a string: a string would not be read as a number
+    NoString,
+    // Operand after a `+` in a string: a string would not be read as a number,
+    // nor after another `+`, which is another concatenation
+    Concat
+}
+
+/// Writes random valid lines, with random spacing, comments and redundant
+/// parentheses.
+struct Generator
+{
+    state: u64,
+    out: String
+}
+
+impl Generator
+{
+    fn new(seed: u64) -> Generator
+    {
+        Generator { state: seed ^ 0x9e37_79b9_7f4a_7c15, out: String::new() }
+    }
+
+    fn below(&mut self, n: u64) -> u64
+    {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_f491_4f6c_dd1d) % n
+    }
+
+    fn push(&mut self, text: &str)
+    {
+        self.out += text;
+    }
+
+    fn space(&mut self)
+    {
+        let space = match self.below(12)
+        {
+            0 => " ",
+            1 => "\n  ",
+            2 => "\t",
+            3 => " /* c */ ",
+            _ => ""
+        };
+        self.push(space);
+    }
+
+    // Pushes `text` surrounded by random spacing
+    fn token(&mut self, text: &str)
+    {
+        self.space();
+        self.push(text);
+        self.space();
+    }
+
+    fn literal(&mut self)
+    {
+        let literal = if self.below(4) == 0
+        {
+            format!("{:#x}", self.below(0x10000))
+        }
+        else
+        {
+            self.below(100_000).to_string()
+        };
+        self.push(&literal);
+    }
+
+    fn absnumber(&mut self, depth: u32)
+    {
+        match self.below(if depth == 0 { 1 } else { 3 })
+        {
+            0 => self.literal(),
+            1 =>
+            {
+                self.token("N(");
+                self.number(depth - 1, Lead::Any);
+                self.token(")");
+            },
+            _ => self.token("read()")
+        }
+    }
+
+    fn string_literal(&mut self)
+    {
+        let literal = match self.below(4)
+        {
+            0 => r#""""#,
+            1 => r#""a b""#,
+            2 => r#""\n""#,
+            _ => r#""say \"hi\"""#
+        };
+        self.push(literal);
+    }
+
+    fn atom(&mut self, depth: u32, lead: Lead)
+    {
+        match self.below(if depth == 0 { 1 } else { 5 })
+        {
+            0 => self.absnumber(depth),
+            1 if lead != Lead::NoParens =>
+            {
+                self.token("(");
+                self.number(depth - 1, Lead::Any);
+                self.token(")");
+            },
+            2 if lead == Lead::Any || lead == Lead::NoParens =>
+                self.string_literal(),
+            3 if lead == Lead::Any || lead == Lead::NoParens =>
+            {
+                self.token("U(");
+                self.absnumber(depth - 1);
+                self.token(")");
+            },
+            _ => self.absnumber(depth)
+        }
+    }
+
+    fn term(&mut self, depth: u32, mut lead: Lead)
+    {
+        for _ in 0..self.below(3)
+        {
+            let op = if self.below(2) == 0 { "-" } else { "+" };
+            self.token(op);
+            lead = Lead::Any;
+        }
+        self.atom(depth, lead);
+    }
+
+    fn number(&mut self, depth: u32, lead: Lead)
+    {
+        self.term(depth, lead);
+        for _ in 0..self.below(if depth == 0 { 1 } else { 3 })
+        {
+            let op = match self.below(4)
+            {
+                0 => "*",
+                1 => "/",
+                2 => "+",
+                _ => "-"
+            };
+            self.token(op);
+            let lead = if op == "+" && lead == Lead::Concat
+            {
+                Lead::Concat
+            }
+            else
+            {
+                Lead::Any
+            };
+            self.term(depth - 1, lead);
+        }
+    }
+
+    fn bool_operand(&mut self, depth: u32)
+    {
+        match self.below(if depth == 0 { 1 } else { 3 })
+        {
+            0 => self.number(depth, Lead::NoParens),
+            1 =>
+            {
+                self.token("!");
+                self.bool_operand(depth - 1);
+            },
+            _ =>
+            {
+                self.token("(");
+                self.boolean(depth - 1);
+                self.token(")");
+            }
+        }
+    }
+
+    fn comparison(&mut self, depth: u32)
+    {
+        if self.below(2) == 0
+        {
+            self.bool_operand(depth);
+            return;
+        }
+        self.number(depth, Lead::Any);
+        let op = ["<", "<=", ">", ">=", "==", "!="][self.below(6) as usize];
+        self.token(op);
+        self.number(depth, Lead::Any);
+    }
+
+    fn boolean(&mut self, depth: u32)
+    {
+        for i in 0..=self.below(3)
+        {
+            if i > 0
+            {
+                self.token("||");
+            }
+            for j in 0..=self.below(3)
+            {
+                if j > 0
+                {
+                    self.token("&&");
+                }
+                self.comparison(depth);
+            }
+        }
+    }
+
+    fn string(&mut self, depth: u32)
+    {
+        match self.below(3)
+        {
+            0 => self.string_literal(),
+            1 =>
+            {
+                self.token("U(");
+                self.absnumber(depth);
+                self.token(")");
+            },
+            _ => self.number(depth, Lead::NoString)
+        }
+        for _ in 0..self.below(3)
+        {
+            self.token("+");
+            match self.below(3)
+            {
+                0 => self.string_literal(),
+                1 =>
+                {
+                    self.token("U(");
+                    self.absnumber(depth);
+                    self.token(")");
+                },
+                _ => self.number(depth, Lead::Concat)
+            }
+        }
+    }
+
+    fn statement(&mut self, depth: u32)
+    {
+        match self.below(if depth == 0 { 2 } else { 5 })
+        {
+            0 =>
+            {
+                for i in 0..=self.below(4)
+                {
+                    if i > 0
+                    {
+                        self.token(",");
+                    }
+                    self.number(depth, Lead::Any);
+                    if self.below(2) == 0
+                    {
+                        self.token("#");
+                        self.number(depth, Lead::Any);
+                    }
+                }
+            },
+            1 =>
+            {
+                self.token("print(");
+                self.string(depth);
+                self.token(")");
+            },
+            n =>
+            {
+                self.push(["again", "defer", "forget"][n as usize - 2]);
+                self.token("(");
+                self.boolean(depth - 1);
+                self.token(")");
+                self.statement(depth - 1);
+            }
+        }
+    }
+
+    fn line(&mut self, depth: u32)
+    {
+        self.literal();
+        self.push(" ");
+        self.space();
+        self.statement(depth);
+        self.token(";");
+    }
+}
+
+/// Records the tree in pre-order, leaving out parentheses.
+///
+/// As every node has a fixed number of children, the sequence identifies the
+/// tree.
+#[derive(Default)]
+struct Shape(Vec<String>);
+
+macro_rules! record
+{
+    ($($visit: ident, $walk: ident, $name: ident;)*) =>
+    {
+        $(
+            fn $visit(&mut self, node: &$name<'a>)
+            {
+                self.0.push(String::from(stringify!($name)));
+                $walk(self, node)
+            }
+        )*
+    };
+}
+
+impl<'a> Visitor<'a> for Shape
+{
+    record!(visit_line, walk_line, Line;
+            visit_unopnumber, walk_unopnumber, UnOpNumber;
+            visit_binopnumber, walk_binopnumber, BinOpNumber;
+            visit_unopboolean, walk_unopboolean, UnOpBoolean;
+            visit_binopboolean, walk_binopboolean, BinOpBoolean;
+            visit_binopnumboolean, walk_binopnumboolean, BinOpNumBoolean;
+            visit_n, walk_n, N;
+            visit_read, walk_read, Read;
+            visit_countlineop, walk_countlineop, CountLineOp;
+            visit_lineoplist, walk_lineoplist, LineOpList;
+            visit_again, walk_again, Again;
+            visit_defer, walk_defer, Defer;
+            visit_forget, walk_forget, Forget;
+            visit_print, walk_print, Print;
+            visit_concat, walk_concat, Concat;
+            visit_u, walk_u, U;
+            visit_numtobool, walk_numtobool, NumToBool;
+            visit_numtolineop, walk_numtolineop, NumToLineOp;
+            visit_numtostring, walk_numtostring, NumToString;
+            visit_stringtonum, walk_stringtonum, StringToNum;);
+
+    fn visit_number_token(&mut self, node: &NumberToken<'a>)
+    {
+        self.0.push(node.val.to_string());
+    }
+    fn visit_string_token(&mut self, node: &StringToken<'a>)
+    {
+        self.0.push(format!("{:?}", node.val));
+    }
+    fn visit_plus_token(&mut self, _node: &PlusToken<'a>)
+    {
+        self.0.push(String::from("+"));
+    }
+    fn visit_minus_token(&mut self, _node: &MinusToken<'a>)
+    {
+        self.0.push(String::from("-"));
+    }
+    fn visit_mathop_token(&mut self, node: &MathOpToken<'a>)
+    {
+        self.0.push(format!("{:?}", node.val));
+    }
+    fn visit_binboolop_token(&mut self, node: &BinBoolOpToken<'a>)
+    {
+        self.0.push(format!("{:?}", node.val));
+    }
+    fn visit_binnumboolop_token(&mut self, node: &BinNumBoolOpToken<'a>)
+    {
+        self.0.push(format!("{:?}", node.val));
+    }
+}
+
+fn shape(program: &Program) -> Vec<String>
+{
+    let mut shape = Shape::default();
+    shape.visit_program(program);
+    shape.0
+}
+
+// Checks that formatting `input` keeps its tree, and that formatting again
+// does not change anything
+fn check_roundtrip(input: &str, style: &Style)
+{
+    let program = whenever_parser::parse_program(input)
+        .unwrap_or_else(|error| panic!("{} in:\n{}", error, input));
+    let formatted = format::format_program(&program, style);
+    let reparsed = whenever_parser::parse_program(&formatted)
+        .unwrap_or_else(|error| panic!("{} in:\n{}\nformatted from:\n{}",
+                                       error, formatted, input));
+    assert_eq!(shape(&program), shape(&reparsed),
+               "\n{}\nformatted as:\n{}", input, formatted);
+    assert_eq!(format::format_program(&reparsed, style), formatted,
+               "\nformatting is not idempotent for:\n{}", input);
+}
+
+#[test]
+fn roundtrip_lines()
+{
+    let styles = [
+        Style::default(),
+        Style { spaced_operators: false, ..Style::default() },
+        Style { width: 20, indent: 1, ..Style::default() }
+    ];
+    for seed in 0..1000
+    {
+        let mut generator = Generator::new(seed);
+        generator.line(4);
+        for style in &styles
+        {
+            check_roundtrip(&generator.out, style);
+        }
+    }
+}
+
+#[test]
+fn roundtrip_programs()
+{
+    let style = Style { sort_lines: true, ..Style::default() };
+    for seed in 0..100
+    {
+        let mut generator = Generator::new(seed);
+        for _ in 0..=generator.below(8)
+        {
+            generator.line(3);
+            let end = if generator.below(3) == 0 { "\n\n" } else { "\n" };
+            generator.push(end);
+        }
+        check_roundtrip(&generator.out, &Style::default());
+
+        // Sorting keeps the lines, in another order
+        let program = whenever_parser::parse_program(&generator.out).unwrap();
+        let sorted = format::format_program(&program, &style);
+        let resorted = whenever_parser::parse_program(&sorted).unwrap();
+        let mut lines: Vec<_> = program.lines.iter()
+            .map(|line| line.as_line().unwrap())
+            .map(|line| format::format_line(line, &Style::default()))
+            .collect();
+        let mut relines: Vec<_> = resorted.lines.iter()
+            .map(|line| line.as_line().unwrap())
+            .map(|line| format::format_line(line, &Style::default()))
+            .collect();
+        lines.sort();
+        relines.sort();
+        assert_eq!(lines, relines);
+    }
+}
@@ -1,4 +1,9 @@
-use whenever_parser;
+use std::thread;
+
+use whenever_parser::ast::{Graph, LineOpsKind, StatementKind, StringKind};
+use whenever_parser::format::{self, Style};
+use whenever_parser::interp::Interpreter;
+use whenever_parser::io::Memory;
 
 #[test]
 fn smoke_single_line()
@@ -9,6 +14,87 @@ fn smoke_single_line()
     whenever_parser::parse_line(input).unwrap();
 }
 
+#[test]
+fn smoke_trailing_input()
+{
+    let input = "1 2; 2";
+
+    let error = whenever_parser::parse_line(input).err().unwrap();
+    assert_eq!(error.kind, whenever_parser::ErrorKind::TrailingInput);
+    assert_eq!(error.to_string(), "1:6: expected end of input, found `2`");
+}
+
+#[test]
+fn smoke_nesting_too_deep()
+{
+    // Would overflow the stack without a maximum depth
+    let input = format!("1 {}2{};", "(".repeat(100_000), ")".repeat(100_000));
+
+    let error = whenever_parser::parse_line(&input).err().unwrap();
+    assert_eq!(error.kind, whenever_parser::ErrorKind::NestingTooDeep);
+    assert_eq!(error.to_string(), "1:68: nesting too deep at `(`");
+
+    let input = format!("1 {}2;\n2 3;\n", "again (1) ".repeat(100_000));
+    let (program, errors) = whenever_parser::parse_program_recovering(&input);
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].kind, whenever_parser::ErrorKind::NestingTooDeep);
+    assert!(program.lines[1].as_line().is_some());
+}
+
+#[test]
+fn smoke_long_lists()
+{
+    // Lists and chains of operators are nested as deep as they are long, yet
+    // reading, walking and dropping long ones fits in the stack of a new
+    // thread
+    let list = thread::Builder::new().stack_size(2 << 20).spawn(|| {
+        let input = format!("1 {};", vec!["1"; 200_000].join(", "));
+        let line = whenever_parser::parse_line(&input).unwrap();
+        let mut lineops = match &line.stmt.alt
+        {
+            StatementKind::LineOperations(operations) => &operations.lineops,
+            _ => panic!("Not a list of line operations")
+        };
+        let mut len = 1;
+        while let LineOpsKind::List(list) = &lineops.alt
+        {
+            lineops = &list.list;
+            len += 1;
+        }
+        drop(line);
+        len
+    }).unwrap().join().unwrap();
+    assert_eq!(list, 200_000);
+
+    let output = thread::Builder::new().stack_size(2 << 20).spawn(|| {
+        let input = format!("1 defer ({}) print({} + \"!\" + {});",
+                            vec!["0"; 10_000].join(" || "),
+                            vec!["1"; 200_000].join(" + "),
+                            vec!["\"a\""; 10_000].join(" + "));
+        let program = whenever_parser::parse_program(&input).unwrap();
+        program.to_dot();
+        let formatted = format::format_program(&program, &Style::default());
+        assert!(formatted.ends_with(" + \"a\");\n"));
+        let mut io = Memory::new("");
+        Interpreter::with_seed(&program, 0).unwrap().run(&mut io).unwrap();
+        io.output
+    }).unwrap().join().unwrap();
+    assert_eq!(output, format!("200000!{}\n", "a".repeat(10_000)));
+}
+
+#[test]
+fn smoke_multiline()
+{
+    // First line of fibo.wnvr, split over several physical lines
+    let input = "1 again (1)
+    defer (3 || N(1)<=N(2) || N(7)>99)
+    2#N(1),3,7;
+";
+
+    let program = whenever_parser::parse_program(input).unwrap();
+    assert_eq!(program.lines.len(), 1);
+}
+
 #[test]
 fn smoke_hello()
 {
@@ -44,3 +130,47 @@ fn smoke_fibonacci()
         whenever_parser::parse_line(line).unwrap();
     }
 }
+
+#[test]
+fn smoke_program_hello()
+{
+    // Program for 2 "Hello world!"s
+    let program = include_str!("hello.wnvr");
+
+    let program = whenever_parser::parse_program(program).unwrap();
+    let line = program.lines[0].as_line().unwrap();
+    let print = match &line.stmt.alt
+    {
+        StatementKind::Print(print) => print,
+        _ => panic!("Not a print")
+    };
+    match &print.string.alt
+    {
+        StringKind::String(string) =>
+        {
+            assert_eq!(string.tok, "\"Hello world!\"");
+            assert_eq!(string.val, "Hello world!");
+        }
+        _ => panic!("Not a string literal")
+    }
+}
+
+#[test]
+fn smoke_program_beer()
+{
+    // Program for "99 bottles of beer"
+    let program = include_str!("beer.wnvr");
+
+    let program = whenever_parser::parse_program(program).unwrap();
+    assert_eq!(program.lines.len(), 4);
+}
+
+#[test]
+fn smoke_program_fibonacci()
+{
+    // Program for the first 100 Fibonacci numbers
+    let program = include_str!("fibo.wnvr");
+
+    let program = whenever_parser::parse_program(program).unwrap();
+    assert_eq!(program.lines.len(), 9);
+}